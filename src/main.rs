@@ -1,28 +1,53 @@
 mod picker;
 
+use crate::picker::bindings::Bindings;
 use crate::picker::commands::Command;
+use crate::picker::config;
+use crate::picker::input::Input;
 use crate::picker::modes::Mode;
-use crate::picker::options::Options;
+use crate::picker::options::{Format, Options};
 use crate::picker::picker::Picker;
 use crate::picker::select_action::SelectAction;
 use crate::picker::ui::Ui;
-use clap::{crate_authors, crate_version, Arg};
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use std::error::Error;
+use clap::{crate_authors, crate_version, Arg, ArgAction, ArgGroup, ArgMatches};
+use crossterm::event::{Event, KeyEvent, KeyEventKind};
+use anyhow::{bail, Context, Result};
 use std::io;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
+use std::process;
+use std::time::Duration;
 
-fn main() {
-    let matches = clap::Command::new("pickline")
+fn command() -> clap::Command {
+    clap::Command::new("pickline")
         .version(crate_version!())
         .author(crate_authors!())
         .about("pickline: a tool to pick lines")
+        // options from the config file, PICKLINE_DEFAULT_OPTS and profiles come first, so later ones have to win
+        .args_override_self(true)
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .help("use the options of a profile from the config file")
+        )
         .arg(
             Arg::new("page_size")
                 .long("page-size")
                 .help("a number or 'auto' to have the pages fit the terminal size")
                 .default_value("auto")
         )
+        .arg(
+            Arg::new("scroll")
+                .long("scroll")
+                .action(ArgAction::SetTrue)
+                .help("scroll the list line by line instead of moving between pages")
+        )
+        .arg(
+            Arg::new("scroll-off")
+                .long("scroll-off")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0")
+                .help("the number of lines to keep visible above and below the cursor when scrolling")
+        )
         .arg(
             Arg::new("alphabet")
                 .long("alphabet")
@@ -34,20 +59,188 @@ fn main() {
             Arg::new("delimiter")
                 .long("delimiter")
                 .short('d')
-                .help("split on delimiter and treat lines as columnar data")
+                .help("split on delimiter and treat lines as columnar data - a single space splits on runs of whitespace, like awk")
+        )
+        .arg(
+            Arg::new("delimiter-regex")
+                .long("delimiter-regex")
+                .help("split on matches of a regex and treat lines as columnar data")
+                .conflicts_with_all(["delimiter", "format"])
+        )
+        .arg(
+            Arg::new("max-split")
+                .long("max-split")
+                .value_parser(clap::value_parser!(usize))
+                .help("split lines at most N times, so the last column keeps the rest of the line (requires -d or --delimiter-regex)")
+                .requires("columnar")
+                .conflicts_with("format")
+        )
+        .arg(
+            Arg::new("output-delimiter")
+                .long("output-delimiter")
+                .help("join output columns with this instead of the delimiter, which defaults to a space for --delimiter-regex and -d ' '")
+                .requires("columnar")
+                .conflicts_with("format")
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_parser(["csv", "tsv", "jsonl"])
+                .help("parse lines as csv or tsv records with quoted fields, which may span multiple lines - implies -d ',' or -d '\\t' - or as json lines, where columns are field paths like .metadata.name")
         )
+        .arg(
+            Arg::new("header-lines")
+                .long("header-lines")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0")
+                .help("show the first N lines as a header above the list instead of picking them - with -d, columns can be referred to by their name in the header")
+        )
+        .arg(
+            Arg::new("read0")
+                .long("read0")
+                .action(ArgAction::SetTrue)
+                .help("read lines separated by nul instead of newlines, like the output of `find -print0`")
+        )
+        .group(ArgGroup::new("columnar").args(["delimiter", "delimiter-regex", "format"]).multiple(true))
         .arg(
             Arg::new("columns")
                 .long("cols")
                 .short('c')
-                .help("the columns to display (requires -d)")
-                .requires("delimiter")
+                .help("the columns to display (requires -d or --format)")
+                .requires("columnar")
         )
         .arg(
             Arg::new("output-columns")
                 .long("output-cols")
-                .help("the columns to output - will be joined by delimiter (requires -d)")
-                .requires("delimiter")
+                .help("the columns to output - will be joined by delimiter, or tabs for --format jsonl (requires -d or --format)")
+                .requires("columnar")
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_parser(["text", "json", "jsonl"])
+                .default_value("text")
+                .help("output selected lines as text, a json array or json lines - json includes the line, its index and its columns, as well as its fields by name with --header-lines - or the object itself with --format jsonl")
+                .conflicts_with_all(["output-columns", "output-format"])
+        )
+        .arg(
+            Arg::new("output-format")
+                .long("output-format")
+                .help("a template for the output, where {N} is column N, {} the whole line, {n} the line number (starting at 0) and {q} the filter")
+                .conflicts_with("output-columns")
+        )
+        .arg(
+            Arg::new("output-order")
+                .long("output-order")
+                .value_parser(["input", "selection", "reverse"])
+                .default_value("selection")
+                .help("the order selected lines are output in - reverse outputs the most recently selected line first")
+        )
+        .arg(
+            Arg::new("print0")
+                .long("print0")
+                .action(ArgAction::SetTrue)
+                .help("end output lines with nul instead of a newline, for `xargs -0`")
+        )
+        .arg(
+            Arg::new("filter-columns")
+                .long("filter-cols")
+                .help("the columns the filter is matched against (requires -d or --format)")
+                .requires("columnar")
+        )
+        .arg(
+            Arg::new("match")
+                .long("match")
+                .short('m')
+                .value_parser(["fuzzy", "exact", "regex"])
+                .default_value("fuzzy")
+                .help("how the filter is matched against lines (can be cycled with <tab> in filter mode)")
+        )
+        .arg(
+            Arg::new("query")
+                .long("query")
+                .short('q')
+                .help("start with the filter set to the given query")
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .short('f')
+                .help("print every line matching the given query and exit, without starting the interactive ui")
+                .conflicts_with("query")
+        )
+        .arg(
+            Arg::new("preview")
+                .long("preview")
+                .help("a command to preview the line under the cursor with, using the same placeholders as --output-format")
+        )
+        .arg(
+            Arg::new("preview-window")
+                .long("preview-window")
+                .help("where to show the preview, 'right' or 'bottom' optionally followed by a size, e.g. 'right:40%' or 'bottom:10'")
+                .default_value("right:50%")
+        )
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .action(ArgAction::Append)
+                .help("bind keys to actions, e.g. 'ctrl-j:move-down,alt-a:toggle-all' - prefix a key with a mode to bind it in that mode, e.g. 'filter/ctrl-j:move-down', and use 'ignore' to unbind it")
+        )
+        .arg(
+            Arg::new("bind-file")
+                .long("bind-file")
+                .help("a file with a binding per line, in the same format as --bind")
+        )
+        .arg(
+            Arg::new("highlight-style")
+                .long("highlight-style")
+                .help("the style of characters matching the filter, e.g. 'red+bold' or '#ff8700+underline'")
+                .default_value("underline")
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .action(ArgAction::Append)
+                .help("set the style of elements, e.g. 'cursor:green+bold,marker:yellow' - elements are cursor, marker, hint, match, bar, border, header and colN for column N")
+        )
+        .arg(
+            Arg::new("no-color")
+                .long("no-color")
+                .action(ArgAction::SetTrue)
+                .help("don't use any colors, which is also the case when NO_COLOR is set")
+        )
+        .arg(
+            Arg::new("ansi")
+                .long("ansi")
+                .action(ArgAction::SetTrue)
+                .help("show the colors of input containing ansi escape sequences, like `git log --color` - the output is stripped of them")
+        )
+        .arg(
+            Arg::new("keep-ansi")
+                .long("keep-ansi")
+                .action(ArgAction::SetTrue)
+                .requires("ansi")
+                .help("keep the colors of --ansi in the output")
+        )
+        .arg(
+            Arg::new("tabstop")
+                .long("tabstop")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("8")
+                .help("the width of a tab stop, which tabs are expanded to when shown")
+        )
+        .arg(
+            Arg::new("pointer")
+                .long("pointer")
+                .default_value(">")
+                .help("the string shown in front of the line under the cursor")
+        )
+        .arg(
+            Arg::new("marker")
+                .long("marker")
+                .default_value("+")
+                .help("the string shown in front of selected lines")
         )
         .arg(
             Arg::new("selection-regex")
@@ -55,87 +248,140 @@ fn main() {
                 .help("regex used to determine initial selection")
                 .default_value("\\S")
         )
-        .get_matches();
+}
 
-    let opts = Options::from_matches(&matches);
+fn main() {
+    let command = command();
+    let args = config::args(&command).unwrap_or_else(|e| {
+        eprintln!("pickline: {}", e);
+        process::exit(2);
+    });
 
-    match run(opts.unwrap()) {
-        Err(_) => panic!("error oh no real bad"),
-        Ok(None) => (),
-        Ok(Some(lines)) => {
+    let matches = command.get_matches_from(args);
+
+    let outcome = run(&matches);
+
+    let code = match outcome {
+        Ok(Outcome::Selected(lines)) => {
+            let terminator = if matches.get_flag("print0") { b'\0' } else { b'\n' };
+            let mut stdout = io::stdout().lock();
             for l in lines {
-                println!("{}", l);
+                let _ = stdout.write_all(&l).and_then(|_| stdout.write_all(&[terminator]));
             }
+
+            // exiting doesn't flush stdout, which only happens by itself at the end of a line
+            let _ = stdout.flush();
+            0
         },
-    }
+        Ok(Outcome::Nothing) => 1,
+        Ok(Outcome::Cancelled) => 130,
+        Err(e) => {
+            eprintln!("pickline: {:#}", e);
+            2
+        },
+    };
+
+    process::exit(code);
 }
 
-fn run(opts: Options) -> Result<Option<Vec<String>>, Box<dyn Error>> {
-    let lines = io::stdin().lines().map(|l| l.unwrap()).collect::<Vec<String>>();
+// how picking ended, which determines the exit code
+enum Outcome {
+    Selected(Vec<Vec<u8>>),
+    Nothing,
+    Cancelled,
+}
+
+fn run(matches: &ArgMatches) -> Result<Outcome> {
+    // the format is parsed once, as a clone shares its compiled delimiter regexes
+    let format = Format::from_matches(matches).map_err(anyhow::Error::msg)?;
+    let mut input = Input::spawn(format.clone(), matches.get_flag("read0"));
+
+    // columns can be referred to by the names in the header, so it has to be read before the options are parsed
+    let header = input.header(*matches.get_one::<usize>("header-lines").unwrap()).context("unable to read input")?
+        .iter()
+        .map(|h| String::from_utf8_lossy(h).into_owned())
+        .collect();
+    let opts = Options::from_matches(matches, format, header).map_err(anyhow::Error::msg)?;
+
+    if let Some(query) = &opts.filter {
+        return filter(input, opts.clone(), query.to_string());
+    }
 
     let mut w = BufWriter::new(io::stderr());
-    let mut picker = Picker::new(&lines, opts.clone());
-    let mut ui = Ui::new(&picker, opts.clone());
+    let mut picker = Picker::new(opts.clone());
+    if let Some(query) = &opts.query {
+        // lines are filtered as they arrive, so the query has to be in place before the first one
+        picker.persist_filter(query.to_string());
+        picker.apply_filter(query.to_string());
+    }
+
+    let mut ui = Ui::new(opts.clone()).context("unable to access the terminal")?;
+
+    // the terminal has to be restored, even if picking fails
+    let picked = ui.setup(&mut w).and_then(|_| pick(&mut input, &mut picker, &mut ui, &opts.bindings, &mut w));
+    ui.cleanup(&mut w)?;
+
+    match picked? {
+        true => Ok(picker.result().map_or(Outcome::Nothing, Outcome::Selected)),
+        false => Ok(Outcome::Cancelled),
+    }
+}
+
+// outputs every line matching the query, the way selected lines would be output
+fn filter(input: Input, opts: Options, query: String) -> Result<Outcome> {
+    let lines = input.collect().context("unable to read input")?;
 
-    ui.setup(&mut w)?;
+    let mut picker = Picker::new(opts);
+    picker.push_lines(&lines);
+    picker.persist_filter(query.clone());
+
+    let matching = picker.apply_filter(query);
+    Ok(picker.result_for(matching.as_slice()).map_or(Outcome::Nothing, Outcome::Selected))
+}
 
+// runs the event loop until lines are picked, returning false if picking is cancelled
+fn pick(input: &mut Input, picker: &mut Picker, ui: &mut Ui, bindings: &Bindings, w: &mut impl Write) -> Result<bool> {
+    let mut redraw = true;
     loop {
-        ui.draw(&mut w, &picker)?;
-
-        let (key_code, modifiers) = next_keycode()?;
-
-        let command = match ui.mode() {
-            Mode::Normal => {
-               match key_code {
-                   KeyCode::Char(' ') if modifiers.contains(KeyModifiers::CONTROL) => Some(Command::ToggleSelectionForVisible(SelectAction::None)),
-                   KeyCode::Enter => Some(Command::ToggleSelection(SelectAction::Exit)),
-                   KeyCode::Char(' ') => Some(Command::ToggleSelection(SelectAction::None)),
-                   KeyCode::Char('j') | KeyCode::Down => Some(Command::MoveDown),
-                   KeyCode::Char('k') | KeyCode::Up => Some(Command::MoveUp),
-                   KeyCode::Char('[') => Some(Command::PreviousPage),
-                   KeyCode::Char(']') => Some(Command::NextPage),
-                   KeyCode::Char('s') => Some(Command::ShowSelection),
-                   KeyCode::Char('f') => Some(Command::EnterMode(Mode::Hint(SelectAction::Exit))),
-                   KeyCode::Char('F') => Some(Command::EnterMode(Mode::Hint(SelectAction::None))),
-                   KeyCode::Char('/') => Some(Command::EnterMode(Mode::Filter)),
-                   KeyCode::Char('q') | KeyCode::Esc => Some(Command::Exit),
-                   _ => None,
-               }
-            }
-            Mode::Hint(sa) => {
-                match key_code {
-                    KeyCode::Esc => Some(Command::EnterMode(Mode::Normal)),
-                    KeyCode::Backspace => {
-                        Some(Command::RemoveHintChar)
-                    }
-                    KeyCode::Char(c) => {
-                        Some(Command::AddHintChar(c, sa))
-                    },
-                    KeyCode::Enter => Some(Command::Exit),
-                    _ => None,
-                }
-            }
-            Mode::Filter => {
-                match key_code {
-                    KeyCode::Enter => Some(Command::SaveFilter),
-                    KeyCode::Esc => Some(Command::DiscardFilter),
-                    KeyCode::Backspace => {
-                        Some(Command::PopCharFromFilter)
-                    }
-                    KeyCode::Char(c) => {
-                        Some(Command::AddCharToFilter(c))
-                    },
-                    _ => None,
-                }
-            }
-            Mode::DisplaySelection => {
-                match key_code {
-                    KeyCode::Enter => Some(Command::EnterMode(Mode::Normal)),
-                    KeyCode::Esc => Some(Command::EnterMode(Mode::Normal)),
-                    _ => None,
-                }
-            }
+        let lines = input.drain().context("unable to read input")?;
+        if input.done() && picker.lines().is_empty() && lines.is_empty() {
+            bail!("no input");
+        }
+
+        if !lines.is_empty() {
+            let added = picker.push_lines(&lines);
+            ui.update(w, picker, added)?;
+            redraw = true;
+        }
+
+        if input.done() && ui.loading() {
+            ui.set_loading(false);
+            redraw = true;
+        }
+
+        if ui.update_preview(picker) {
+            redraw = true;
+        }
+
+        if redraw {
+            ui.draw(w, picker)?;
+            redraw = false;
+        }
+
+        // keep polling for input while lines are still arriving or the preview is running
+        let timeout = (ui.loading() || ui.previewing()).then(|| Duration::from_millis(50));
+        let (key_code, modifiers) = match next_event(timeout)? {
+            Some(Event::Key(KeyEvent { code, modifiers, .. })) => (code, modifiers),
+            Some(Event::Resize(width, height)) => {
+                ui.resize(w, picker, width, height)?;
+                redraw = true;
+                continue;
+            },
+            _ => continue,
         };
+        redraw = true;
+
+        let command = bindings.command(&ui.mode(), key_code, modifiers);
 
         if let Some(command) = command {
             match command {
@@ -148,6 +394,10 @@ fn run(opts: Options) -> Result<Option<Vec<String>>, Box<dyn Error>> {
                 Command::MoveDown => ui.move_cursor_down(),
                 Command::PreviousPage => ui.previous_page(),
                 Command::NextPage => ui.next_page(),
+                Command::ScrollLeft => ui.scroll_left(),
+                Command::ScrollRight => ui.scroll_right(),
+                Command::ScrollPreviewUp => ui.scroll_preview_up(),
+                Command::ScrollPreviewDown => ui.scroll_preview_down(),
                 Command::AddCharToFilter(c) => {
                     ui.push_to_input_buffer(c);
                     let visible = picker.apply_filter(ui.get_input_buffer());
@@ -163,6 +413,11 @@ fn run(opts: Options) -> Result<Option<Vec<String>>, Box<dyn Error>> {
                     ui.paginate(visible.as_slice());
                     ui.change_mode(Mode::Normal);
                 }
+                Command::CycleMatchMode => {
+                    picker.cycle_match_mode();
+                    let visible = picker.apply_filter(ui.get_input_buffer());
+                    ui.paginate(visible.as_slice());
+                }
                 Command::SaveFilter => {
                     picker.persist_filter(ui.get_input_buffer());
                     ui.change_mode(Mode::Normal);
@@ -177,7 +432,7 @@ fn run(opts: Options) -> Result<Option<Vec<String>>, Box<dyn Error>> {
                         if let Some(choice) = ui.line_under_cursor() {
                             picker.toggle_selection(choice);
                             if select_action == SelectAction::Exit {
-                                break;
+                                return Ok(true);
                             }
                             ui.clear_input_buffer();
                         }
@@ -195,14 +450,24 @@ fn run(opts: Options) -> Result<Option<Vec<String>>, Box<dyn Error>> {
                         picker.toggle_selection(choice);
 
                         if select_action == SelectAction::Exit {
-                            break
+                            return Ok(true)
                         }
                     }
                 },
+                // the line under the cursor is only picked when nothing is selected, and the selection is accepted
+                // even when the filter hides every line
+                Command::SelectAndAccept => {
+                    if !picker.has_selection() && let Some(choice) = ui.line_under_cursor() {
+                        picker.select(choice);
+                    }
+
+                    return Ok(true)
+                },
                 Command::ShowSelection => {
                     ui.change_mode(Mode::DisplaySelection);
                 }
-                Command::Exit => break,
+                Command::Accept => return Ok(true),
+                Command::Cancel => return Ok(false),
                 Command::ToggleSelectionForVisible(select_action) => {
                     if let Some(page) = ui.page() {
                         for line in page {
@@ -211,28 +476,25 @@ fn run(opts: Options) -> Result<Option<Vec<String>>, Box<dyn Error>> {
                     }
 
                     if select_action == SelectAction::Exit {
-                        break
+                        return Ok(true)
                     }
                 }
             }
         }
     }
-
-    ui.cleanup(&mut w)?;
-
-    Ok(picker.result())
 }
 
-fn next_keycode() -> std::io::Result<(KeyCode, KeyModifiers)> {
+// waits for a key press or a change in terminal size, giving up after `timeout` if one is given
+fn next_event(timeout: Option<Duration>) -> std::io::Result<Option<Event>> {
     loop {
-        if let Ok(Event::Key(KeyEvent {
-                                 code,
-                                 kind: KeyEventKind::Press,
-                                 modifiers,
-                                 state: _,
-                             })) = crossterm::event::read()
-        {
-            return Ok((code, modifiers));
+        if let Some(timeout) = timeout && !crossterm::event::poll(timeout)? {
+            return Ok(None);
+        }
+
+        match crossterm::event::read() {
+            Ok(event @ Event::Key(KeyEvent { kind: KeyEventKind::Press, .. })) => return Ok(Some(event)),
+            Ok(event @ Event::Resize(_, _)) => return Ok(Some(event)),
+            _ => {},
         }
     }
 }
@@ -0,0 +1,308 @@
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+// the styles of a text, each given by the char index it starts at
+pub type Styles = Vec<(usize, ContentStyle)>;
+
+// the colors of sgr codes 30 to 37 followed by the bright ones of 90 to 97, and likewise for backgrounds
+const COLORS: [Color; 16] = [
+    Color::Black, Color::DarkRed, Color::DarkGreen, Color::DarkYellow,
+    Color::DarkBlue, Color::DarkMagenta, Color::DarkCyan, Color::Grey,
+    Color::DarkGrey, Color::Red, Color::Green, Color::Yellow,
+    Color::Blue, Color::Magenta, Color::Cyan, Color::White,
+];
+
+// the attributes that are parsed, along with their sgr codes, in the order they're rendered
+const ATTRIBUTES: [(Attribute, u16); 8] = [
+    (Attribute::Bold, 1),
+    (Attribute::Dim, 2),
+    (Attribute::Italic, 3),
+    (Attribute::Underlined, 4),
+    (Attribute::SlowBlink, 5),
+    (Attribute::Reverse, 7),
+    (Attribute::Hidden, 8),
+    (Attribute::CrossedOut, 9),
+];
+
+// removes escape sequences from text, returning the plain text along with the colors and attributes set by sgr
+// sequences in it. any other escape sequence is dropped
+pub fn parse(text: &str) -> (String, Styles) {
+    let mut plain = String::new();
+    let mut styles: Styles = Vec::new();
+    let mut style = ContentStyle::new();
+    let mut chars = 0;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('\x1b') {
+        plain.push_str(&rest[..start]);
+        chars += rest[..start].chars().count();
+        rest = &rest[start..];
+
+        let end = sequence_length(rest);
+        if let Some(params) = rest[..end].strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) {
+            style = apply(style, params);
+            match styles.last_mut() {
+                Some((start, last)) if *start == chars => *last = style,
+                Some((_, last)) if *last == style => {},
+                _ => styles.push((chars, style)),
+            }
+        }
+
+        rest = &rest[end..];
+    }
+
+    plain.push_str(rest);
+
+    (plain, styles)
+}
+
+// the length in bytes of the escape sequence at the start of text
+fn sequence_length(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let end = match bytes.get(1) {
+        // csi, like `\x1b[1;31m`, ends with a byte in the range @ to ~
+        Some(b'[') => bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b)).map(|i| i + 3),
+        // osc, like a hyperlink or window title, ends with bel or `\x1b\`
+        Some(b']') => (2..bytes.len()).find_map(|i| match (bytes[i], bytes.get(i + 1)) {
+            (0x07, _) => Some(i + 1),
+            (0x1b, Some(b'\\')) => Some(i + 2),
+            _ => None,
+        }),
+        // one with intermediate bytes, like the charset selection `\x1b(B` of `tput sgr0`, ends with the byte after them
+        Some(0x20..=0x2f) => bytes[2..].iter().position(|b| (0x30..=0x7e).contains(b)).map(|i| i + 3),
+        Some(b) if b.is_ascii() => Some(2),
+        _ => Some(1),
+    };
+
+    end.unwrap_or(bytes.len())
+}
+
+fn apply(mut style: ContentStyle, params: &str) -> ContentStyle {
+    let mut codes = params.split([';', ':']).map(|c| c.parse::<u16>().unwrap_or(0));
+
+    while let Some(code) = codes.next() {
+        match code {
+            0 => style = ContentStyle::new(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => style.attributes.set(Attribute::Underlined),
+            5 => style.attributes.set(Attribute::SlowBlink),
+            7 => style.attributes.set(Attribute::Reverse),
+            8 => style.attributes.set(Attribute::Hidden),
+            9 => style.attributes.set(Attribute::CrossedOut),
+            22 => {
+                style.attributes.unset(Attribute::Bold);
+                style.attributes.unset(Attribute::Dim);
+            },
+            23 => style.attributes.unset(Attribute::Italic),
+            24 => style.attributes.unset(Attribute::Underlined),
+            25 => style.attributes.unset(Attribute::SlowBlink),
+            27 => style.attributes.unset(Attribute::Reverse),
+            28 => style.attributes.unset(Attribute::Hidden),
+            29 => style.attributes.unset(Attribute::CrossedOut),
+            // basic colors are kept as such, so they're output as they were read with --keep-ansi
+            30..=37 => style.foreground_color = Some(COLORS[code as usize - 30]),
+            38 => style.foreground_color = color(&mut codes),
+            39 => style.foreground_color = None,
+            40..=47 => style.background_color = Some(COLORS[code as usize - 40]),
+            48 => style.background_color = color(&mut codes),
+            49 => style.background_color = None,
+            90..=97 => style.foreground_color = Some(COLORS[code as usize - 90 + 8]),
+            100..=107 => style.background_color = Some(COLORS[code as usize - 100 + 8]),
+            _ => {},
+        }
+    }
+
+    style
+}
+
+// an extended color, either `5;n` for one of 256 colors or `2;r;g;b`
+fn color(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match codes.next() {
+        Some(5) => codes.next().map(|n| Color::AnsiValue(n as u8)),
+        Some(2) => match (codes.next(), codes.next(), codes.next()) {
+            (Some(r), Some(g), Some(b)) => Some(Color::Rgb { r: r as u8, g: g as u8, b: b as u8 }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the styles of each column that `text` was split into, found by looking for the columns in order
+pub fn columns(text: &str, columns: &[String], styles: &Styles) -> Vec<Styles> {
+    if styles.is_empty() {
+        return Vec::new();
+    }
+
+    let mut offset = 0;
+    columns.iter()
+        .map(|column| {
+            // columns that were changed when splitting, like quoted csv fields, can't be found and are left plain
+            let Some(found) = text[offset..].find(column.as_str()) else {
+                return Vec::new();
+            };
+
+            let start = text[..offset + found].chars().count();
+            let end = start + column.chars().count();
+            offset += found + column.len();
+
+            let mut column_styles: Styles = styles.iter().rev().find(|(i, _)| *i <= start).map(|(_, s)| vec![(0, *s)]).unwrap_or_default();
+            column_styles.extend(styles.iter().filter(|(i, _)| *i > start && *i < end).map(|(i, s)| (i - start, *s)));
+            column_styles
+        })
+        .collect()
+}
+
+// the text with its styles as sgr sequences again, using the same codes they were parsed from
+pub fn render(text: &str, styles: &[(usize, ContentStyle)]) -> String {
+    let mut rendered = String::new();
+    let mut style = ContentStyle::new();
+    let mut styles = styles.iter().peekable();
+
+    for (i, c) in text.chars().enumerate() {
+        let mut next = style;
+        while let Some((_, s)) = styles.next_if(|(start, _)| *start <= i) {
+            next = *s;
+        }
+
+        if next != style {
+            if style != ContentStyle::new() {
+                rendered.push_str("\x1b[0m");
+            }
+
+            if next != ContentStyle::new() {
+                rendered.push_str(&format!("\x1b[{}m", sgr(next)));
+            }
+
+            style = next;
+        }
+
+        rendered.push(c);
+    }
+
+    if style != ContentStyle::new() {
+        rendered.push_str("\x1b[0m");
+    }
+
+    rendered
+}
+
+fn sgr(style: ContentStyle) -> String {
+    let mut codes = ATTRIBUTES.iter()
+        .filter(|(attribute, _)| style.attributes.has(*attribute))
+        .map(|(_, code)| code.to_string())
+        .collect::<Vec<String>>();
+
+    codes.extend(style.foreground_color.map(|c| color_code(c, 30, 90, 38)));
+    codes.extend(style.background_color.map(|c| color_code(c, 40, 100, 48)));
+
+    codes.join(";")
+}
+
+// `base` is the code of the first basic color, `bright` that of the first bright one and `extended` the code for
+// 256 and rgb colors
+fn color_code(color: Color, base: u16, bright: u16, extended: u16) -> String {
+    match color {
+        Color::Reset => (base + 9).to_string(),
+        Color::AnsiValue(n) => format!("{};5;{}", extended, n),
+        Color::Rgb { r, g, b } => format!("{};2;{};{};{}", extended, r, g, b),
+        color => match COLORS.iter().position(|c| *c == color).unwrap_or_default() as u16 {
+            i if i < 8 => (base + i).to_string(),
+            i => (bright + i - 8).to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fg(color: Color) -> ContentStyle {
+        ContentStyle { foreground_color: Some(color), ..ContentStyle::new() }
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(parse("plain"), ("plain".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn colors() {
+        let (text, styles) = parse("a\x1b[31mred\x1b[0m b");
+        assert_eq!(text, "ared b");
+        assert_eq!(styles, vec![(1, fg(Color::DarkRed)), (4, ContentStyle::new())]);
+
+        assert_eq!(parse("\x1b[38;5;208mx").1, vec![(0, fg(Color::AnsiValue(208)))]);
+        assert_eq!(parse("\x1b[38;2;1;2;3mx").1, vec![(0, fg(Color::Rgb { r: 1, g: 2, b: 3 }))]);
+        assert_eq!(parse("\x1b[94mx").1, vec![(0, fg(Color::Blue))]);
+    }
+
+    #[test]
+    fn attributes() {
+        let mut bold = ContentStyle::new();
+        bold.attributes.set(Attribute::Bold);
+        let mut bold_red = fg(Color::DarkRed);
+        bold_red.attributes.set(Attribute::Bold);
+
+        let (text, styles) = parse("\x1b[1mb\x1b[31mr\x1b[22mn");
+        assert_eq!(text, "brn");
+        assert_eq!(styles, vec![(0, bold), (1, bold_red), (2, fg(Color::DarkRed))]);
+    }
+
+    #[test]
+    fn sequences_at_the_same_position_are_combined() {
+        let mut bold_red = fg(Color::DarkRed);
+        bold_red.attributes.set(Attribute::Bold);
+
+        assert_eq!(parse("\x1b[1m\x1b[31mx").1, vec![(0, bold_red)]);
+        // a sequence that doesn't change the style doesn't add one
+        assert_eq!(parse("\x1b[31mx\x1b[31my").1, vec![(0, fg(Color::DarkRed))]);
+    }
+
+    #[test]
+    fn positions_are_characters() {
+        assert_eq!(parse("héé\x1b[31mx").1, vec![(3, fg(Color::DarkRed))]);
+    }
+
+    #[test]
+    fn other_sequences_are_dropped() {
+        assert_eq!(parse("\x1b]8;;http://example.com\x1b\\link\x1b]8;;\x07!"), ("link!".to_string(), Vec::new()));
+        assert_eq!(parse("a\x1b[2Kb\x1b(Bc"), ("abc".to_string(), Vec::new()));
+        assert_eq!(parse("unterminated \x1b[31"), ("unterminated ".to_string(), Vec::new()));
+        assert_eq!(parse("a\x1b"), ("a".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn column_styles() {
+        let (text, styles) = parse("a \x1b[31mbc d\x1b[0m e");
+        let cols = ["a", "bc d", "e"].map(String::from);
+        assert_eq!(columns(&text, &cols, &styles), vec![
+            Vec::new(),
+            vec![(0, fg(Color::DarkRed))],
+            vec![(0, ContentStyle::new())],
+        ]);
+
+        let cols = ["a b", "c d e"].map(String::from);
+        assert_eq!(columns(&text, &cols, &styles), vec![
+            vec![(2, fg(Color::DarkRed))],
+            vec![(0, fg(Color::DarkRed)), (3, ContentStyle::new())],
+        ]);
+    }
+    #[test]
+    fn render_writes_the_codes_that_were_parsed() {
+        for text in [
+            "\x1b[31mred\x1b[0m",
+            "a\x1b[1;94mb\x1b[0mc",
+            "\x1b[41mx\x1b[0m\x1b[102my\x1b[0m",
+            "\x1b[38;5;208mx\x1b[0m \x1b[48;2;1;2;3my\x1b[0m",
+        ] {
+            let (plain, styles) = parse(text);
+            assert_eq!(render(&plain, &styles), text);
+        }
+    }
+
+    #[test]
+    fn render_resets_at_the_end() {
+        assert_eq!(render("red", &[(0, fg(Color::DarkRed))]), "\x1b[31mred\x1b[0m");
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use crossterm::event::{KeyCode, KeyModifiers};
+use crate::picker::commands::Command;
+use crate::picker::modes::Mode;
+use crate::picker::select_action::SelectAction;
+
+// the default bindings, in the same format as --bind
+const DEFAULTS: &[&str] = &[
+    "ctrl-space:toggle-all",
+    "enter:select-and-accept",
+    "space:toggle",
+    "j:move-down",
+    "down:move-down",
+    "k:move-up",
+    "up:move-up",
+    "[:previous-page",
+    "]:next-page",
+    "h:scroll-left",
+    "left:scroll-left",
+    "l:scroll-right",
+    "right:scroll-right",
+    "K:preview-up",
+    "J:preview-down",
+    "s:show-selection",
+    "f:hint",
+    "F:multi-hint",
+    "/:filter",
+    "q:cancel",
+    "esc:cancel",
+    "hint/esc:normal-mode",
+    "hint/backspace:backspace",
+    "hint/enter:accept",
+    "filter/enter:save-filter",
+    "filter/esc:discard-filter",
+    "filter/tab:cycle-match",
+    "filter/backspace:backspace",
+    "selection/enter:normal-mode",
+    "selection/esc:normal-mode",
+];
+
+// maps key chords to commands for each mode, keys bound to `None` are ignored
+#[derive(Clone)]
+pub struct Bindings {
+    keys: HashMap<(BindingMode, Key), Option<Command>>,
+}
+
+// hint mode shares its bindings, regardless of whether picking a hint exits
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum BindingMode {
+    Normal,
+    Hint,
+    Filter,
+    Selection,
+}
+
+impl BindingMode {
+    fn of(mode: &Mode) -> Self {
+        match mode {
+            Mode::Normal => BindingMode::Normal,
+            Mode::Hint(_) => BindingMode::Hint,
+            Mode::Filter => BindingMode::Filter,
+            Mode::DisplaySelection => BindingMode::Selection,
+        }
+    }
+}
+
+impl FromStr for BindingMode {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<BindingMode, ()> {
+        match input.to_lowercase().as_str() {
+            "normal" => Ok(BindingMode::Normal),
+            "hint" => Ok(BindingMode::Hint),
+            "filter" => Ok(BindingMode::Filter),
+            "selection" => Ok(BindingMode::Selection),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    // shift is part of the character itself, so it is left out to match however the terminal reports it
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(_) | KeyCode::BackTab => Key { code, modifiers: modifiers - KeyModifiers::SHIFT },
+            _ => Key { code, modifiers },
+        }
+    }
+}
+
+// parses keys like `ctrl-j`, `alt-enter` or `F`
+impl FromStr for Key {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Key, ()> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = input;
+        while let Some((modifier, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => break,
+            }
+
+            name = rest;
+        }
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backspace" | "bspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "space" => KeyCode::Char(' '),
+                "comma" => KeyCode::Char(','),
+                "colon" => KeyCode::Char(':'),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pgup" => KeyCode::PageUp,
+                "pgdn" => KeyCode::PageDown,
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(()),
+                },
+            },
+        };
+
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+impl Bindings {
+    // the default bindings, overridden by the given bindings in order
+    pub fn new(bindings: &[String]) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+
+        let defaults = DEFAULTS.iter().map(|b| b.to_string());
+        for binding in defaults.chain(bindings.iter().flat_map(|b| Self::split(b))) {
+            let (mode, key, command) = Self::parse(&binding)?;
+            keys.insert((mode, key), command);
+        }
+
+        Ok(Self { keys })
+    }
+
+    // splits a list of bindings, a comma is only a separator when it isn't the key being bound
+    fn split(bindings: &str) -> Vec<String> {
+        let mut split: Vec<String> = Vec::new();
+        for part in bindings.split(',') {
+            match split.last_mut() {
+                Some(last) if last.is_empty() || last.ends_with('/') => {
+                    last.push(',');
+                    last.push_str(part);
+                },
+                _ => split.push(part.to_string()),
+            }
+        }
+
+        split.into_iter().map(|b| b.trim().to_string()).filter(|b| !b.is_empty()).collect()
+    }
+
+    // parses a binding like `ctrl-j:move-down` or `filter/ctrl-j:move-down`
+    fn parse(binding: &str) -> Result<(BindingMode, Key, Option<Command>), String> {
+        let Some((key, action)) = binding.rsplit_once(':').filter(|(key, _)| !key.is_empty()) else {
+            return Err(format!("invalid binding '{}', expected 'key:action'", binding));
+        };
+
+        let (mode, key) = match key.split_once('/').filter(|(mode, key)| !mode.is_empty() && !key.is_empty()) {
+            Some((mode, key)) => {
+                let mode = BindingMode::from_str(mode)
+                    .map_err(|_| format!("unknown mode '{}' in binding '{}'", mode, binding))?;
+                (mode, key)
+            },
+            None => (BindingMode::Normal, key),
+        };
+
+        let key = Key::from_str(key).map_err(|_| format!("unknown key '{}' in binding '{}'", key, binding))?;
+        let command = Self::action(mode, action).ok_or_else(|| format!("unknown action '{}' in binding '{}'", action, binding))?;
+
+        Ok((mode, key, command))
+    }
+
+    fn action(mode: BindingMode, action: &str) -> Option<Option<Command>> {
+        let command = match action.to_lowercase().as_str() {
+            "ignore" => return Some(None),
+            "move-up" => Command::MoveUp,
+            "move-down" => Command::MoveDown,
+            "previous-page" => Command::PreviousPage,
+            "next-page" => Command::NextPage,
+            "scroll-left" => Command::ScrollLeft,
+            "scroll-right" => Command::ScrollRight,
+            "preview-up" => Command::ScrollPreviewUp,
+            "preview-down" => Command::ScrollPreviewDown,
+            "toggle" => Command::ToggleSelection(SelectAction::None),
+            "toggle-and-accept" => Command::ToggleSelection(SelectAction::Exit),
+            "toggle-all" => Command::ToggleSelectionForVisible(SelectAction::None),
+            "toggle-all-and-accept" => Command::ToggleSelectionForVisible(SelectAction::Exit),
+            "select-and-accept" => Command::SelectAndAccept,
+            "show-selection" => Command::ShowSelection,
+            "normal-mode" => Command::EnterMode(Mode::Normal),
+            "hint" => Command::EnterMode(Mode::Hint(SelectAction::Exit)),
+            "multi-hint" => Command::EnterMode(Mode::Hint(SelectAction::None)),
+            "filter" => Command::EnterMode(Mode::Filter),
+            "save-filter" => Command::SaveFilter,
+            "discard-filter" => Command::DiscardFilter,
+            "cycle-match" => Command::CycleMatchMode,
+            "backspace" => match mode {
+                BindingMode::Hint => Command::RemoveHintChar,
+                BindingMode::Filter => Command::PopCharFromFilter,
+                _ => return None,
+            },
+            "accept" => Command::Accept,
+            "cancel" => Command::Cancel,
+            _ => return None,
+        };
+
+        Some(Some(command))
+    }
+
+    // the command bound to a key in the given mode, keys that aren't bound are typed in filter and hint mode
+    pub fn command(&self, mode: &Mode, code: KeyCode, modifiers: KeyModifiers) -> Option<Command> {
+        let key = Key::new(code, modifiers);
+        if let Some(command) = self.keys.get(&(BindingMode::of(mode), key)) {
+            return command.clone();
+        }
+
+        match (mode, key.code) {
+            (Mode::Hint(select_action), KeyCode::Char(c)) if key.modifiers.is_empty() => Some(Command::AddHintChar(c, select_action.clone())),
+            (Mode::Filter, KeyCode::Char(c)) if key.modifiers.is_empty() => Some(Command::AddCharToFilter(c)),
+            _ => None,
+        }
+    }
+}
@@ -1,20 +1,28 @@
 use crate::picker::modes::Mode;
 use crate::picker::select_action::SelectAction;
 
+#[derive(Clone)]
 pub(crate) enum Command {
     EnterMode(Mode),
     MoveUp,
     MoveDown,
     PreviousPage,
     NextPage,
+    ScrollLeft,
+    ScrollRight,
+    ScrollPreviewUp,
+    ScrollPreviewDown,
     ToggleSelection(SelectAction),
     ToggleSelectionForVisible(SelectAction),
+    SelectAndAccept,
     ShowSelection,
     AddCharToFilter(char),
     PopCharFromFilter,
     DiscardFilter,
     SaveFilter,
+    CycleMatchMode,
     AddHintChar(char, SelectAction),
     RemoveHintChar,
-    Exit,
+    Accept,
+    Cancel,
 }
@@ -0,0 +1,265 @@
+use clap::Command;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+// the arguments to parse, made up of the options in the config file, PICKLINE_DEFAULT_OPTS, the chosen profile and
+// finally the command line, so that later options take precedence over earlier ones
+pub fn args(command: &Command) -> Result<Vec<String>, String> {
+    let mut cli = env::args();
+    let program = cli.next().unwrap_or_else(|| "pickline".to_string());
+    let cli: Vec<String> = cli.collect();
+
+    let config = read_config()?;
+    let default_opts = match env::var("PICKLINE_DEFAULT_OPTS") {
+        Ok(opts) => split_words(&opts).map_err(|e| format!("invalid PICKLINE_DEFAULT_OPTS: {}", e))?,
+        Err(_) => Vec::new(),
+    };
+
+    let mut sources = vec![to_args(&config, "config")?, default_opts.clone()];
+
+    if let Some(name) = profile(default_opts.iter().chain(cli.iter())) {
+        let profile = config.get("profile")
+            .and_then(Value::as_table)
+            .and_then(|profiles| profiles.get(&name))
+            .and_then(Value::as_table)
+            .ok_or_else(|| format!("unknown profile '{}'", name))?;
+
+        sources.push(to_args(profile, &format!("profile '{}'", name))?);
+    }
+
+    let mut args = vec![program];
+    args.extend(without_conflicts(command, &sources, &cli));
+    args.extend(cli);
+
+    Ok(args)
+}
+
+// the options of every source in order, leaving out the ones that conflict with an option given later, so that e.g.
+// `--format jsonl` on the command line replaces a delimiter from the config file instead of being an error
+fn without_conflicts(command: &Command, sources: &[Vec<String>], cli: &[String]) -> Vec<String> {
+    let mut given: Vec<String> = options(command, cli).into_iter().filter_map(|(id, _)| id).collect();
+    let mut kept = Vec::new();
+
+    for source in sources.iter().rev() {
+        let options = options(command, source);
+        kept.push(options.iter()
+            .filter(|(id, _)| !id.as_ref().is_some_and(|id| given.iter().any(|other| conflicts(command, id, other))))
+            .flat_map(|(_, words)| words.iter().cloned())
+            .collect::<Vec<_>>());
+        given.extend(options.into_iter().filter_map(|(id, _)| id));
+    }
+
+    kept.into_iter().rev().flatten().collect()
+}
+
+// splits arguments into options, each with the id of the argument it sets, if known, and its words
+fn options(command: &Command, args: &[String]) -> Vec<(Option<String>, Vec<String>)> {
+    let mut options = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(word) = args.next() {
+        let (arg, has_value) = if let Some(long) = word.strip_prefix("--") {
+            let (name, has_value) = long.split_once('=').map_or((long, false), |(name, _)| (name, true));
+            (command.get_arguments().find(|arg| arg.get_long() == Some(name)), has_value)
+        } else if let Some(short) = word.strip_prefix('-') && let Some(c) = short.chars().next() {
+            (command.get_arguments().find(|arg| arg.get_short() == Some(c)), short.len() > c.len_utf8())
+        } else {
+            (None, true)
+        };
+
+        let mut words = vec![word.clone()];
+        if let Some(arg) = arg && arg.get_action().takes_values() && !has_value {
+            words.extend(args.next().cloned());
+        }
+
+        options.push((arg.map(|arg| arg.get_id().to_string()), words));
+    }
+
+    options
+}
+
+// whether two arguments can't be used together, where the arguments of a group, like the delimiter and format
+// options, count as alternatives of each other
+fn conflicts(command: &Command, a: &str, b: &str) -> bool {
+    let conflicts_with = |a: &str, b: &str| command.get_arguments()
+        .find(|arg| arg.get_id() == a)
+        .is_some_and(|arg| command.get_arg_conflicts_with(arg).iter().any(|other| other.get_id() == b));
+    let grouped = command.get_groups()
+        .any(|group| group.get_args().any(|id| id == a) && group.get_args().any(|id| id == b));
+
+    a != b && (grouped || conflicts_with(a, b) || conflicts_with(b, a))
+}
+
+fn read_config() -> Result<Table, String> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    let Some(path) = dir.map(|dir| dir.join("pickline").join("config.toml")) else {
+        return Ok(Table::new());
+    };
+
+    if !path.exists() {
+        return Ok(Table::new());
+    }
+
+    let config = fs::read_to_string(&path).map_err(|e| format!("unable to read '{}': {}", path.display(), e))?;
+    config.parse().map_err(|e| format!("invalid config '{}': {}", path.display(), e))
+}
+
+// the profile given by the last --profile option, if any
+fn profile<'a>(args: impl Iterator<Item = &'a String>) -> Option<String> {
+    let mut profile = None;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            profile = args.peek().map(|p| p.to_string());
+        } else if let Some(p) = arg.strip_prefix("--profile=") {
+            profile = Some(p.to_string());
+        }
+    }
+
+    profile
+}
+
+// turns `name = value` pairs into options, e.g. `page-size = 10` into `--page-size=10` and `scroll = true` into
+// `--scroll`, an array repeats the option for every value
+fn to_args(table: &Table, source: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+
+    for (name, value) in table {
+        if name == "profile" && source == "config" {
+            continue;
+        }
+
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+
+        for value in values {
+            match value {
+                Value::String(s) => args.push(format!("--{}={}", name, s)),
+                Value::Integer(n) => args.push(format!("--{}={}", name, n)),
+                Value::Boolean(true) => args.push(format!("--{}", name)),
+                Value::Boolean(false) => {},
+                _ => return Err(format!("invalid value for '{}' in {}", name, source)),
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+// splits options the way a shell would, e.g. `--delimiter ' ' --cols 1..` into four words
+fn split_words(input: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                let escaped = chars.next().ok_or("trailing backslash")?;
+                word.get_or_insert_default().push(escaped);
+            },
+            (Some(_), c) => word.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            },
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_default().push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+
+    words.extend(word);
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, ArgGroup};
+
+    fn words(input: &str) -> Vec<String> {
+        split_words(input).unwrap()
+    }
+
+    fn command() -> Command {
+        Command::new("test")
+            .arg(Arg::new("delimiter").long("delimiter").short('d'))
+            .arg(Arg::new("delimiter-regex").long("delimiter-regex"))
+            .arg(Arg::new("format").long("format"))
+            .group(ArgGroup::new("columnar").args(["delimiter", "delimiter-regex", "format"]).multiple(true))
+            .arg(Arg::new("output").long("output").short('o').conflicts_with("output-columns"))
+            .arg(Arg::new("output-columns").long("output-cols"))
+            .arg(Arg::new("scroll").long("scroll").action(ArgAction::SetTrue))
+    }
+
+    fn merge(sources: &[&str], cli: &str) -> Vec<String> {
+        let sources = sources.iter().map(|s| words(s)).collect::<Vec<_>>();
+        without_conflicts(&command(), &sources, &words(cli))
+    }
+
+    #[test]
+    fn split_words_like_a_shell() {
+        assert_eq!(words("  --scroll   -a asdf "), vec!["--scroll", "-a", "asdf"]);
+        assert_eq!(words("--delimiter ' ' --cols 1.."), vec!["--delimiter", " ", "--cols", "1.."]);
+        assert_eq!(words("a\\ b \"c d\" 'e\\f'"), vec!["a b", "c d", "e\\f"]);
+        assert_eq!(words("\"a\\\"b\" x''y"), vec!["a\"b", "xy"]);
+        assert_eq!(words("'' \"\""), vec!["", ""]);
+        assert!(words("").is_empty());
+    }
+
+    #[test]
+    fn split_words_errors() {
+        assert!(split_words("'abc").is_err());
+        assert!(split_words("\"abc").is_err());
+        assert!(split_words("abc\\").is_err());
+    }
+
+    #[test]
+    fn profile_is_the_last_one_given() {
+        let args = words("--profile a --scroll --profile=b");
+        assert_eq!(profile(args.iter()), Some("b".to_string()));
+        assert_eq!(profile(words("--scroll").iter()), None);
+    }
+
+    #[test]
+    fn table_to_args() {
+        let table = "page-size = 10\nscroll = true\nansi = false\ndelimiter = \" \"\nbind = [\"q:ignore\", \"j:move-down\"]"
+            .parse::<Table>()
+            .unwrap();
+        let mut args = to_args(&table, "config").unwrap();
+        args.sort();
+        assert_eq!(args, vec!["--bind=j:move-down", "--bind=q:ignore", "--delimiter= ", "--page-size=10", "--scroll"]);
+
+        let table = "cols = 1.5".parse::<Table>().unwrap();
+        assert!(to_args(&table, "config").is_err());
+    }
+
+    #[test]
+    fn options_without_conflicts_are_kept() {
+        assert_eq!(merge(&["--delimiter=, --scroll", "-o json"], "--scroll"), words("--delimiter=, --scroll -o json"));
+        assert_eq!(merge(&["--delimiter=,"], "-d ;"), words("--delimiter=,"));
+    }
+
+    #[test]
+    fn later_options_replace_conflicting_ones() {
+        assert_eq!(merge(&["--delimiter=, --scroll"], "--delimiter-regex ;"), words("--scroll"));
+        assert_eq!(merge(&["-d , --scroll"], "--format jsonl"), words("--scroll"));
+        assert_eq!(merge(&["--output-cols 1", "--output json"], ""), words("--output json"));
+        assert_eq!(merge(&["--output json", "--output-cols=1"], ""), words("--output-cols=1"));
+        assert_eq!(merge(&["-o json --scroll", ""], "--output-cols 1"), words("--scroll"));
+    }
+}
@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+
+// splits a record into its fields following rfc 4180, where a field can be quoted to contain the delimiter,
+// line breaks and quotes (escaped as ""). the bool is false if the record ends inside a quoted field
+pub fn split(record: &str, delimiter: char) -> (Vec<String>, bool) {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut field_start = true;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' if quoted => quoted = false,
+            // quotes only start a quoted field at the start of it, elsewhere they are taken literally
+            '"' if field_start => quoted = true,
+            c if c == delimiter && !quoted => {
+                fields.push(std::mem::take(&mut field));
+                field_start = true;
+                continue;
+            },
+            c => field.push(c),
+        }
+
+        field_start = false;
+    }
+
+    fields.push(field);
+
+    (fields, !quoted)
+}
+
+// whether a record is inside a quoted field at the end of a line of it, given whether it was at the start of the line,
+// so a record spanning many lines doesn't have to be split again for every line
+pub fn is_quoted(line: &str, delimiter: char, mut quoted: bool) -> bool {
+    let mut field_start = !quoted;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+            },
+            '"' if quoted => quoted = false,
+            '"' if field_start => quoted = true,
+            c if c == delimiter && !quoted => {
+                field_start = true;
+                continue;
+            },
+            _ => {},
+        }
+
+        field_start = false;
+    }
+
+    quoted
+}
+
+pub fn join(fields: &[String], delimiter: char) -> String {
+    fields.iter().map(|f| quote(f, delimiter)).collect::<Vec<_>>().join(delimiter.to_string().as_str())
+}
+
+// quotes a field if it contains anything that would otherwise be mistaken for the end of it
+fn quote(field: &str, delimiter: char) -> Cow<'_, str> {
+    match field.contains([delimiter, '"', '\n', '\r']) {
+        true => Cow::Owned(format!("\"{}\"", field.replace('"', "\"\""))),
+        false => Cow::Borrowed(field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(record: &str) -> Vec<String> {
+        let (fields, complete) = split(record, ',');
+        assert!(complete);
+        fields
+    }
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(fields("a,b,,c"), vec!["a", "b", "", "c"]);
+        assert_eq!(fields(""), vec![""]);
+        assert_eq!(split("a;b,c", ';').0, vec!["a", "b,c"]);
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(fields("\"a,b\",c"), vec!["a,b", "c"]);
+        assert_eq!(fields("\"say \"\"hi\"\"\",x"), vec!["say \"hi\"", "x"]);
+        assert_eq!(fields("\"\"\"\""), vec!["\""]);
+        assert_eq!(fields("\"\",x"), vec!["", "x"]);
+    }
+
+    #[test]
+    fn quotes_mid_field() {
+        assert_eq!(fields("a\"b,c"), vec!["a\"b", "c"]);
+        assert_eq!(fields("a \"b,c\""), vec!["a \"b", "c\""]);
+        assert_eq!(fields("\"a\"b,c"), vec!["ab", "c"]);
+    }
+
+    #[test]
+    fn multi_line() {
+        assert_eq!(fields("a,\"b\nc\",d"), vec!["a", "b\nc", "d"]);
+        assert!(!split("a,\"b", ',').1);
+        assert!(!split("a,\"b\"\"", ',').1);
+    }
+
+    #[test]
+    fn quote_state_across_lines() {
+        assert!(is_quoted("a,\"b", ',', false));
+        assert!(!is_quoted("a,b\"", ',', false));
+        assert!(is_quoted("c \"\"d", ',', true));
+        assert!(!is_quoted("e\",f", ',', true));
+        assert!(is_quoted("e\",\"f", ',', true));
+        assert!(!is_quoted("", ',', false));
+        assert!(is_quoted("", ',', true));
+    }
+
+    #[test]
+    fn quote_state_agrees_with_split() {
+        let record = "a,\"b\n\"\"c\n\",d\"\ne\"";
+        let mut quoted = false;
+        let mut lines = Vec::new();
+        for line in record.split('\n') {
+            lines.push(line);
+            quoted = is_quoted(line, ',', quoted);
+            assert_eq!(quoted, !split(&lines.join("\n"), ',').1);
+        }
+    }
+
+    #[test]
+    fn join_quotes_where_needed() {
+        let fields = ["a".to_string(), "b,c".to_string(), "say \"hi\"".to_string(), "d\ne".to_string()];
+        assert_eq!(join(&fields, ','), "a,\"b,c\",\"say \"\"hi\"\"\",\"d\ne\"");
+        assert_eq!(join(&["b,c".to_string()], ';'), "b,c");
+    }
+
+    #[test]
+    fn round_trip() {
+        let records = ["a,b,c", "\"a,b\",\"c\"\"d\"", "x,\"multi\nline\",", "\"\"\"\",,"];
+        for record in records {
+            let fields = fields(record);
+            assert_eq!(fields, split(&join(&fields, ','), ',').0);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::io;
+use std::io::BufRead;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+
+use crate::picker::options::Format;
+
+// reads stdin on a background thread, so the ui can be drawn while lines are still arriving. lines are read as bytes,
+// as they don't have to be valid utf-8
+pub struct Input {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    done: bool,
+}
+
+impl Input {
+    // lines are joined into records for formats where a record can span multiple lines. with `read0`, lines are
+    // separated by nul instead of newlines
+    pub fn spawn(format: Option<Format>, read0: bool) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let separator = if read0 { b'\0' } else { b'\n' };
+            let lines = io::stdin().lock().split(separator).map(|line| match (read0, line) {
+                // like `BufRead::lines`, a line can end with \r\n as well
+                (false, Ok(mut line)) => {
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+
+                    Ok(line)
+                },
+                (_, line) => line,
+            });
+
+            let mut record: Option<Vec<u8>> = None;
+            for line in lines {
+                let continues = match (&line, &format) {
+                    (Ok(line), Some(format)) => format.continues(&String::from_utf8_lossy(line), record.is_some()),
+                    _ => false,
+                };
+
+                let line = match (line, record.take()) {
+                    (Ok(line), Some(mut record)) => {
+                        record.push(separator);
+                        record.extend(line);
+                        Ok(record)
+                    },
+                    (line, _) => line,
+                };
+
+                match line {
+                    Ok(line) if continues => record = Some(line),
+                    line => {
+                        let failed = line.is_err();
+                        if tx.send(line).is_err() || failed {
+                            return;
+                        }
+                    },
+                }
+            }
+
+            // a record that never ends is passed on as it is
+            if let Some(record) = record {
+                let _ = tx.send(Ok(record));
+            }
+        });
+
+        Self { rx, done: false }
+    }
+
+    // returns every line received since the last call, without blocking
+    pub fn drain(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut lines = Vec::new();
+
+        loop {
+            match self.rx.try_recv() {
+                Ok(line) => lines.push(line?),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+
+        Ok(lines)
+    }
+
+    // blocks until the first `n` lines have been read, or there are no more lines
+    pub fn header(&mut self, n: usize) -> io::Result<Vec<Vec<u8>>> {
+        let mut lines = Vec::with_capacity(n);
+        while lines.len() < n {
+            match self.rx.recv() {
+                Ok(line) => lines.push(line?),
+                Err(_) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+
+        Ok(lines)
+    }
+
+    // blocks until every line has been read
+    pub fn collect(self) -> io::Result<Vec<Vec<u8>>> {
+        self.rx.iter().collect()
+    }
+
+    pub fn done(&self) -> bool {
+        self.done
+    }
+}
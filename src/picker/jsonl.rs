@@ -0,0 +1,38 @@
+use serde_json::Value;
+
+// parses a line of json lines input, a line that isn't valid json is kept as a string
+pub fn parse(line: &str) -> Value {
+    serde_json::from_str(line).unwrap_or_else(|_| Value::String(line.to_string()))
+}
+
+// the value at each path as text, where the path `.` is the line itself
+pub fn fields(value: &Value, paths: &[String], line: &str) -> Vec<String> {
+    paths.iter()
+        .map(|path| match path.as_str() {
+            "." => line.to_string(),
+            path => get(value, path).map(text).unwrap_or_default(),
+        })
+        .collect()
+}
+
+// a path is `.` or a list of keys like `.metadata.name`, where a number indexes into an array, e.g. `.items.0`
+pub fn is_path(path: &str) -> bool {
+    path == "." || path.strip_prefix('.').is_some_and(|keys| keys.split('.').all(|key| !key.is_empty()))
+}
+
+fn get<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').skip(1).try_fold(value, |value, key| match value {
+        Value::Object(object) => object.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+// strings are shown without quotes and null as nothing, like `jq -r` does
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
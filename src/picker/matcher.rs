@@ -0,0 +1,359 @@
+use regex::Regex;
+use std::cmp::max;
+use std::str::FromStr;
+
+// scoring loosely follows fzf: matched characters score points, gaps cost points,
+// and characters at the start of words and columns earn bonuses
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_COLUMN: i64 = 10;
+const BONUS_BOUNDARY_WHITE: i64 = 10;
+const BONUS_BOUNDARY_DELIMITER: i64 = 9;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_NON_WORD: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    Fuzzy,
+    Exact,
+    Regex,
+}
+
+impl FromStr for MatchMode {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<MatchMode, ()> {
+        match input.to_lowercase().as_str() {
+            "fuzzy" => Ok(MatchMode::Fuzzy),
+            "exact" => Ok(MatchMode::Exact),
+            "regex" => Ok(MatchMode::Regex),
+            _ => Err(()),
+        }
+    }
+}
+
+impl MatchMode {
+    pub fn next(self) -> Self {
+        match self {
+            MatchMode::Fuzzy => MatchMode::Exact,
+            MatchMode::Exact => MatchMode::Regex,
+            MatchMode::Regex => MatchMode::Fuzzy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MatchMode::Fuzzy => "fuzzy",
+            MatchMode::Exact => "exact",
+            MatchMode::Regex => "regex",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    White,
+    NonWord,
+    Delimiter,
+    Lower,
+    Upper,
+    Number,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        match c {
+            c if c.is_lowercase() => CharClass::Lower,
+            c if c.is_uppercase() => CharClass::Upper,
+            c if c.is_numeric() => CharClass::Number,
+            c if c.is_alphabetic() => CharClass::Lower,
+            c if c.is_whitespace() => CharClass::White,
+            '/' | ',' | ':' | ';' | '|' => CharClass::Delimiter,
+            _ => CharClass::NonWord,
+        }
+    }
+
+    fn is_word(self) -> bool {
+        matches!(self, CharClass::Lower | CharClass::Upper | CharClass::Number)
+    }
+}
+
+// a successful match, with the (column index, character index) of every matched character
+pub struct Match {
+    pub score: i64,
+    pub positions: Vec<(usize, usize)>,
+}
+
+// how a single query term is matched against the columns of a line
+#[derive(Clone, Copy, PartialEq)]
+pub enum MatchKind {
+    Fuzzy,
+    Exact,
+    Prefix,
+    Suffix,
+    Equal,
+    Regex,
+}
+
+pub struct Matcher {
+    kind: MatchKind,
+    pattern: String,
+    chars: Vec<char>,
+    regex: Option<Regex>,
+    case_sensitive: bool,
+}
+
+impl Matcher {
+    pub fn new(kind: MatchKind, pattern: &str) -> Self {
+        // smart case: case is ignored as long as the pattern is all lowercase
+        let case_sensitive = kind == MatchKind::Regex || pattern.chars().any(char::is_uppercase);
+
+        Self {
+            kind,
+            pattern: pattern.to_string(),
+            chars: pattern.chars().collect(),
+            regex: (kind == MatchKind::Regex).then(|| Regex::new(pattern).ok()).flatten(),
+            case_sensitive,
+        }
+    }
+
+    // matches the columns, returning None if they don't match. columns are given along
+    // with their index in the line, as the query may only be matched against some of them
+    pub fn find(&self, cols: &[(usize, &str)]) -> Option<Match> {
+        match self.kind {
+            MatchKind::Fuzzy => self.find_fuzzy(cols),
+            MatchKind::Regex => {
+                let regex = self.regex.as_ref()?;
+                Self::find_in_columns(cols, |col| regex.find(col).map(|m| Self::char_range(col, m.start(), m.end())))
+            },
+            _ => Self::find_in_columns(cols, |col| self.find_literal(col)),
+        }
+    }
+
+    // collects the positions of `find` in every column, matching if any column matches
+    fn find_in_columns<'a>(cols: &[(usize, &'a str)], find: impl Fn(&'a str) -> Option<(usize, usize)>) -> Option<Match> {
+        let mut found = false;
+        let mut positions = Vec::new();
+        for (i, col) in cols {
+            if let Some((start, end)) = find(col) {
+                positions.extend((start..end).map(|c| (*i, c)));
+                found = true;
+            }
+        }
+
+        found.then_some(Match { score: 0, positions })
+    }
+
+    // returns the character range of the match within the column
+    fn find_literal(&self, col: &str) -> Option<(usize, usize)> {
+        // characters are folded one by one, so the character indexes of the match are the same as in the column
+        let folded;
+        let col = match self.case_sensitive {
+            true => col,
+            false => {
+                folded = col.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect::<String>();
+                folded.as_str()
+            },
+        };
+
+        let pattern = self.pattern.as_str();
+        let (start, end) = match self.kind {
+            MatchKind::Prefix => col.starts_with(pattern).then_some((0, pattern.len())),
+            MatchKind::Suffix => col.ends_with(pattern).then(|| (col.len() - pattern.len(), col.len())),
+            MatchKind::Equal => (col == pattern).then_some((0, col.len())),
+            _ => col.find(pattern).map(|start| (start, start + pattern.len())),
+        }?;
+
+        Some(Self::char_range(col, start, end))
+    }
+
+    fn char_range(text: &str, start: usize, end: usize) -> (usize, usize) {
+        let first = text[..start].chars().count();
+        (first, first + text[start..end].chars().count())
+    }
+
+    // the fuzzy match may span several columns, so the columns are treated as one sequence
+    // of characters where the first character of each column earns a bonus
+    fn find_fuzzy(&self, cols: &[(usize, &str)]) -> Option<Match> {
+        if self.chars.is_empty() {
+            return Some(Match { score: 0, positions: Vec::new() });
+        }
+
+        let mut text = Vec::new();
+        let mut bonuses = Vec::new();
+        let mut origins = Vec::new();
+        for (col_idx, col) in cols {
+            let mut prev = None;
+            for (char_idx, c) in col.chars().enumerate() {
+                origins.push((*col_idx, char_idx));
+                let class = CharClass::of(c);
+                bonuses.push(match prev {
+                    None => BONUS_COLUMN,
+                    Some(prev) => Self::bonus(prev, class),
+                });
+
+                text.push(if self.case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) });
+                prev = Some(class);
+            }
+        }
+
+        // find the first occurrence of the pattern, then scan backwards to narrow the window
+        let mut pattern_idx = 0;
+        let mut end = None;
+        for (i, c) in text.iter().enumerate() {
+            if *c == self.chars[pattern_idx] {
+                pattern_idx += 1;
+                if pattern_idx == self.chars.len() {
+                    end = Some(i + 1);
+                    break;
+                }
+            }
+        }
+
+        let end = end?;
+        let mut start = end;
+        let mut pattern_idx = self.chars.len();
+        while pattern_idx > 0 {
+            start -= 1;
+            if text[start] == self.chars[pattern_idx - 1] {
+                pattern_idx -= 1;
+            }
+        }
+
+        let mut score = 0;
+        let mut positions = Vec::with_capacity(self.chars.len());
+        let mut pattern_idx = 0;
+        let mut in_gap = false;
+        let mut consecutive = 0;
+        let mut first_bonus = 0;
+        for i in start..end {
+            if pattern_idx < self.chars.len() && text[i] == self.chars[pattern_idx] {
+                let mut bonus = bonuses[i];
+                if consecutive == 0 {
+                    first_bonus = bonus;
+                } else {
+                    if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                        first_bonus = bonus;
+                    }
+                    bonus = max(max(bonus, first_bonus), BONUS_CONSECUTIVE);
+                }
+
+                score += SCORE_MATCH + match pattern_idx {
+                    0 => bonus * BONUS_FIRST_CHAR_MULTIPLIER,
+                    _ => bonus,
+                };
+
+                positions.push(origins[i]);
+                in_gap = false;
+                consecutive += 1;
+                pattern_idx += 1;
+            } else {
+                score += if in_gap { SCORE_GAP_EXTENSION } else { SCORE_GAP_START };
+                in_gap = true;
+                consecutive = 0;
+                first_bonus = 0;
+            }
+        }
+
+        Some(Match { score, positions })
+    }
+
+    fn bonus(prev: CharClass, class: CharClass) -> i64 {
+        match (prev, class) {
+            (CharClass::White, c) if c.is_word() => BONUS_BOUNDARY_WHITE,
+            (CharClass::Delimiter, c) if c.is_word() => BONUS_BOUNDARY_DELIMITER,
+            (CharClass::NonWord, c) if c.is_word() => BONUS_BOUNDARY,
+            (CharClass::Lower, CharClass::Upper) => BONUS_CAMEL,
+            (p, CharClass::Number) if p != CharClass::Number => BONUS_CAMEL,
+            (_, CharClass::NonWord | CharClass::Delimiter) => BONUS_NON_WORD,
+            (_, CharClass::White) => BONUS_BOUNDARY_WHITE,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(kind: MatchKind, pattern: &str, text: &str) -> Option<Match> {
+        Matcher::new(kind, pattern).find(&[(0, text)])
+    }
+
+    fn score(pattern: &str, text: &str) -> i64 {
+        find(MatchKind::Fuzzy, pattern, text).map(|m| m.score).unwrap()
+    }
+
+    fn positions(kind: MatchKind, pattern: &str, text: &str) -> Vec<usize> {
+        find(kind, pattern, text).map(|m| m.positions.into_iter().map(|(_, i)| i).collect()).unwrap()
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(find(MatchKind::Exact, "foo", "a FOO b").is_some());
+        assert!(find(MatchKind::Exact, "Foo", "a FOO b").is_none());
+        assert!(find(MatchKind::Exact, "Foo", "a Foo b").is_some());
+        assert!(find(MatchKind::Fuzzy, "fb", "FooBar").is_some());
+        assert!(find(MatchKind::Fuzzy, "fB", "foobar").is_none());
+    }
+
+    #[test]
+    fn literal_kinds() {
+        assert!(find(MatchKind::Prefix, "ab", "abc").is_some());
+        assert!(find(MatchKind::Prefix, "bc", "abc").is_none());
+        assert!(find(MatchKind::Suffix, "bc", "abc").is_some());
+        assert!(find(MatchKind::Suffix, "ab", "abc").is_none());
+        assert!(find(MatchKind::Equal, "abc", "abc").is_some());
+        assert!(find(MatchKind::Equal, "ab", "abc").is_none());
+        assert!(find(MatchKind::Regex, "a.c", "xabcx").is_some());
+    }
+
+    #[test]
+    fn fuzzy_matches_in_order() {
+        assert!(find(MatchKind::Fuzzy, "abc", "a-b-c").is_some());
+        assert!(find(MatchKind::Fuzzy, "abc", "c-b-a").is_none());
+        assert_eq!(positions(MatchKind::Fuzzy, "fb", "foo bar"), vec![0, 4]);
+    }
+
+    #[test]
+    fn fuzzy_scoring() {
+        assert!(score("abc", "abc") > score("abc", "axbxc"));
+        assert!(score("fb", "foo bar") > score("fb", "xfxxbx"));
+        assert!(score("ab", "xx ab") > score("ab", "xxxab"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+    }
+
+    #[test]
+    fn fuzzy_narrows_to_the_shortest_window() {
+        assert_eq!(positions(MatchKind::Fuzzy, "ab", "a xx ab"), vec![5, 6]);
+    }
+
+    #[test]
+    fn positions_are_characters() {
+        assert_eq!(positions(MatchKind::Exact, "lo", "héllo"), vec![3, 4]);
+        assert_eq!(positions(MatchKind::Regex, "l+", "héllo"), vec![2, 3]);
+    }
+
+    #[test]
+    fn positions_after_case_folding() {
+        // `İ` is lowercased to two characters, which mustn't shift the positions
+        assert_eq!(positions(MatchKind::Exact, "stan", "İstanbul"), vec![1, 2, 3, 4]);
+        assert_eq!(positions(MatchKind::Suffix, "bul", "İSTANBUL"), vec![5, 6, 7]);
+        assert_eq!(positions(MatchKind::Fuzzy, "ib", "İstanbul"), vec![0, 5]);
+    }
+
+    #[test]
+    fn columns() {
+        let matcher = Matcher::new(MatchKind::Exact, "b");
+        let found = matcher.find(&[(0, "ab"), (2, "b")]).unwrap();
+        assert_eq!(found.positions, vec![(0, 1), (2, 0)]);
+
+        let matcher = Matcher::new(MatchKind::Fuzzy, "ab");
+        let found = matcher.find(&[(0, "xa"), (1, "bx")]).unwrap();
+        assert_eq!(found.positions, vec![(0, 1), (1, 0)]);
+    }
+}
@@ -1,6 +1,17 @@
 pub mod ui;
 pub mod commands;
+pub(crate) mod ansi;
+pub(crate) mod bindings;
+pub(crate) mod config;
+pub(crate) mod csv;
+pub(crate) mod jsonl;
 pub(crate) mod picker;
+pub(crate) mod input;
+pub(crate) mod matcher;
+pub(crate) mod query;
+pub(crate) mod style;
+pub(crate) mod template;
+pub(crate) mod preview;
 pub(crate) mod modes;
 pub(crate) mod options;
 pub(crate) mod select_action;
@@ -1,5 +1,14 @@
+use std::cmp::min;
+use std::ops::Range;
 use std::str::FromStr;
 use clap::ArgMatches;
+use regex::Regex;
+use crate::picker::bindings::Bindings;
+use crate::picker::csv;
+use crate::picker::jsonl;
+use crate::picker::matcher::MatchMode;
+use crate::picker::style::{parse_style, Theme};
+use crate::picker::template::Template;
 
 #[derive(Clone)]
 pub enum ColumnRange {
@@ -7,6 +16,15 @@ pub enum ColumnRange {
     Open(Vec<usize>),
 }
 
+impl ColumnRange {
+    pub fn contains(&self, column: usize) -> bool {
+        match self {
+            ColumnRange::Closed(range) => range.contains(&column),
+            ColumnRange::Open(range) => range.last().is_some_and(|last| column > *last) || range.contains(&column),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum PageSizeOption {
     Auto,
@@ -14,90 +32,588 @@ pub enum PageSizeOption {
 }
 
 impl FromStr for PageSizeOption {
-    type Err = ();
+    type Err = String;
 
-    fn from_str(input: &str) -> Result<PageSizeOption, ()> {
+    fn from_str(input: &str) -> Result<PageSizeOption, String> {
         match input.to_lowercase().as_str() {
             "auto" => Ok(PageSizeOption::Auto),
-            _ => Ok(PageSizeOption::Value(input.parse().unwrap()))
+            _ => match input.parse() {
+                Ok(n) if n > 0 => Ok(PageSizeOption::Value(n)),
+                _ => Err(format!("invalid page size '{}', expected a positive number or 'auto'", input)),
+            }
+        }
+    }
+}
+
+// the order selected lines are output in
+#[derive(Clone, Copy)]
+pub enum OutputOrder {
+    Input,
+    Selection,
+    Reverse,
+}
+
+impl FromStr for OutputOrder {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<OutputOrder, ()> {
+        match input.to_lowercase().as_str() {
+            "input" => Ok(OutputOrder::Input),
+            "selection" => Ok(OutputOrder::Selection),
+            "reverse" => Ok(OutputOrder::Reverse),
+            _ => Err(()),
         }
     }
 }
 
+// how selected lines are written to stdout
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    Text,
+    Json,
+    JsonLines,
+}
+
+impl FromStr for OutputMode {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<OutputMode, ()> {
+        match input.to_lowercase().as_str() {
+            "text" => Ok(OutputMode::Text),
+            "json" => Ok(OutputMode::Json),
+            "jsonl" => Ok(OutputMode::JsonLines),
+            _ => Err(()),
+        }
+    }
+}
+
+// how columnar input is split into columns, and how output columns are joined
+#[derive(Clone)]
+pub enum Format {
+    // split at most `max_split` times, so the last column keeps the rest of the line. `bytes` matches the delimiter
+    // in lines that aren't valid utf-8
+    Delimited { delimiter: Delimiter, max_split: Option<usize>, output: String, bytes: regex::bytes::Regex },
+    // csv records, where fields can be quoted
+    Csv(char),
+    // json lines, where the columns are the values at the given paths
+    Json(Vec<String>),
+}
+
+#[derive(Clone)]
+pub enum Delimiter {
+    Literal(String),
+    Regex(Regex),
+    // like awk, runs of whitespace separate columns and leading and trailing whitespace is ignored
+    Whitespace,
+}
+
+impl Format {
+    // the format given by --format or the delimiter options, or None if the input isn't columnar
+    pub fn from_matches(matches: &ArgMatches) -> Result<Option<Format>, String> {
+        let delimiter = matches.get_one::<String>("delimiter");
+        match matches.get_one::<String>("format") {
+            Some(format) if format == "jsonl" => return Self::json(matches, delimiter).map(Some),
+            Some(format) => return Self::csv(format, delimiter).map(Some),
+            None => {},
+        }
+
+        let delimiter = match (delimiter, matches.get_one::<String>("delimiter-regex")) {
+            (Some(delimiter), _) if delimiter == " " => Delimiter::Whitespace,
+            (Some(delimiter), _) => Delimiter::Literal(delimiter.to_string()),
+            (None, Some(regex)) => Delimiter::Regex(Regex::new(regex)
+                .map_err(|e| format!("invalid delimiter regex '{}': {}", regex, e))?),
+            (None, None) => return Ok(None),
+        };
+
+        let max_split = matches.get_one::<usize>("max-split").copied();
+        Self::delimited(delimiter, max_split, matches.get_one::<String>("output-delimiter")).map(Some)
+    }
+
+    // output columns are joined by `output`, or the delimiter itself if it's literal and a space otherwise
+    pub fn delimited(delimiter: Delimiter, max_split: Option<usize>, output: Option<&String>) -> Result<Format, String> {
+        let output = match (output, &delimiter) {
+            (Some(output), _) => output.to_string(),
+            (None, Delimiter::Literal(delimiter)) => delimiter.to_string(),
+            (None, _) => " ".to_string(),
+        };
+
+        let bytes = match &delimiter {
+            Delimiter::Literal(delimiter) => regex::escape(delimiter),
+            Delimiter::Regex(regex) => regex.as_str().to_string(),
+            Delimiter::Whitespace => r"(?-u)\s+".to_string(),
+        };
+        let bytes = regex::bytes::Regex::new(&bytes).map_err(|e| format!("invalid delimiter '{}': {}", bytes, e))?;
+
+        Ok(Format::Delimited { delimiter, max_split, output, bytes })
+    }
+
+    fn csv(format: &str, delimiter: Option<&String>) -> Result<Format, String> {
+        let delimiter = match (format, delimiter) {
+            (_, Some(delimiter)) => delimiter.as_str(),
+            ("tsv", None) => "\t",
+            _ => ",",
+        };
+
+        let mut chars = delimiter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Format::Csv(c)),
+            _ => Err(format!("invalid delimiter '{}', --format {} requires a single character", delimiter, format)),
+        }
+    }
+
+    // the columns are every path given by --cols, --output-cols and --filter-cols, in that order. the line itself is
+    // the path `.`, which is what is displayed and output unless other paths are given
+    fn json(matches: &ArgMatches, delimiter: Option<&String>) -> Result<Format, String> {
+        if delimiter.is_some() {
+            return Err("--delimiter can't be used with --format jsonl".to_string());
+        }
+
+        let mut paths = Vec::new();
+        if !matches.contains_id("columns") || !matches.contains_id("output-columns") {
+            paths.push(".".to_string());
+        }
+
+        for list in ["columns", "output-columns", "filter-columns"].iter().filter_map(|id| matches.get_one::<String>(id)) {
+            for path in list.split(',').map(str::trim) {
+                if !jsonl::is_path(path) {
+                    return Err(format!("invalid field path '{}' in '{}', expected a path like '.metadata.name'", path, list));
+                }
+
+                if !paths.iter().any(|p| p == path) {
+                    paths.push(path.to_string());
+                }
+            }
+        }
+
+        Ok(Format::Json(paths))
+    }
+
+    // the names of the columns, the paths for json lines and otherwise the first header line split into columns
+    pub fn column_names(&self, header: &[String]) -> Vec<String> {
+        match self {
+            Format::Json(paths) => paths.clone(),
+            _ => header.first().map(|h| self.split(h).iter().map(|n| n.trim().to_string()).collect()).unwrap_or_default(),
+        }
+    }
+
+    pub fn split(&self, text: &str) -> Vec<String> {
+        match self {
+            Format::Delimited { delimiter, max_split, .. } => delimiter.split(text, max_split.map_or(usize::MAX, |n| n + 1)),
+            Format::Csv(delimiter) => csv::split(text, *delimiter).0,
+            Format::Json(paths) => jsonl::fields(&jsonl::parse(text), paths, text),
+        }
+    }
+
+    // the byte ranges of the columns of a line that isn't valid utf-8, split like `split` splits text. only delimited
+    // lines can be split this way
+    pub fn split_bytes(&self, line: &[u8]) -> Option<Vec<Range<usize>>> {
+        let Format::Delimited { delimiter, max_split, bytes, .. } = self else {
+            return None;
+        };
+
+        let (start, end) = match delimiter {
+            Delimiter::Whitespace => (
+                line.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(line.len()),
+                line.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(0, |i| i + 1),
+            ),
+            _ => (0, line.len()),
+        };
+
+        let mut ranges = Vec::new();
+        let mut column = start;
+        for found in bytes.find_iter(&line[start..end.max(start)]).take(max_split.unwrap_or(usize::MAX)) {
+            ranges.push(column..start + found.start());
+            column = start + found.end();
+        }
+
+        ranges.push(column..end.max(column));
+
+        Some(ranges)
+    }
+
+    pub fn join(&self, columns: &[String]) -> String {
+        match self {
+            Format::Delimited { output, .. } => columns.join(output),
+            Format::Csv(delimiter) => csv::join(columns, *delimiter),
+            Format::Json(_) => columns.join("\t"),
+        }
+    }
+
+    // true if the record continues on the next line after this line of it, where `continued` is whether it was
+    // continued from the previous line
+    pub fn continues(&self, line: &str, continued: bool) -> bool {
+        match self {
+            Format::Delimited { .. } | Format::Json(_) => false,
+            Format::Csv(delimiter) => csv::is_quoted(line, *delimiter, continued),
+        }
+    }
+}
+
+impl Delimiter {
+    // splits text into at most `limit` columns
+    fn split(&self, text: &str, limit: usize) -> Vec<String> {
+        match self {
+            Delimiter::Literal(delimiter) => text.splitn(limit, delimiter.as_str()).map(String::from).collect(),
+            Delimiter::Regex(regex) => regex.splitn(text, limit).map(String::from).collect(),
+            Delimiter::Whitespace => {
+                let mut columns = Vec::new();
+                let mut rest = text.trim();
+                while columns.len() + 1 < limit && let Some(end) = rest.find(char::is_whitespace) {
+                    columns.push(rest[..end].to_string());
+                    rest = rest[end..].trim_start();
+                }
+
+                columns.push(rest.to_string());
+                columns
+            },
+        }
+    }
+}
+
+// where the preview is shown, and how many columns or lines it takes up
+#[derive(Clone, Copy)]
+pub struct PreviewWindow {
+    pub position: PreviewPosition,
+    size: PreviewSize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PreviewPosition {
+    Right,
+    Bottom,
+}
+
+#[derive(Clone, Copy)]
+enum PreviewSize {
+    Percent(u16),
+    Value(u16),
+}
+
+impl PreviewWindow {
+    // the size of the preview, given the width or height of the terminal and how much of it the list needs
+    pub fn size(&self, available: u16, reserved: u16) -> u16 {
+        let size = match self.size {
+            PreviewSize::Percent(percent) => (available as u32 * percent as u32 / 100) as u16,
+            PreviewSize::Value(n) => n,
+        };
+
+        min(size, available.saturating_sub(reserved))
+    }
+}
+
+impl FromStr for PreviewWindow {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<PreviewWindow, String> {
+        let invalid = || format!("invalid preview window '{}', expected 'right' or 'bottom', optionally followed by ':size'", input);
+
+        let (position, size) = input.split_once(':').unwrap_or((input, "50%"));
+        let position = match position.to_lowercase().as_str() {
+            "right" => PreviewPosition::Right,
+            "bottom" => PreviewPosition::Bottom,
+            _ => return Err(invalid()),
+        };
+
+        let size = match size.strip_suffix('%') {
+            Some(percent) => match percent.parse() {
+                Ok(n) if n > 0 && n < 100 => PreviewSize::Percent(n),
+                _ => return Err(invalid()),
+            },
+            None => match size.parse() {
+                Ok(n) if n > 0 => PreviewSize::Value(n),
+                _ => return Err(invalid()),
+            },
+        };
+
+        Ok(PreviewWindow { position, size })
+    }
+}
+
 #[derive(Clone)]
 pub struct Options {
     pub page_size: PageSizeOption,
     pub hint_alphabet: String,
-    pub delimiter: Option<String>,
+    pub format: Option<Format>,
     pub display_columns: Option<ColumnRange>,
     pub output_columns: Option<ColumnRange>,
-    pub selection_regex: Option<String>,
+    pub filter_columns: Option<ColumnRange>,
+    pub selection_regex: Option<Regex>,
+    pub match_mode: MatchMode,
+    pub theme: Theme,
+    pub ansi: bool,
+    pub keep_ansi: bool,
+    pub tabstop: usize,
+    pub pointer: String,
+    pub marker: String,
+    pub output_order: OutputOrder,
+    pub output_format: Option<Template>,
+    pub output_mode: OutputMode,
+    pub query: Option<String>,
+    pub filter: Option<String>,
+    pub scroll: bool,
+    pub scroll_off: usize,
+    pub preview: Option<Template>,
+    pub preview_window: PreviewWindow,
+    pub bindings: Bindings,
+    pub header: Vec<String>,
+    pub column_names: Vec<String>,
 }
 
 impl Options {
-    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
-        let page_size = PageSizeOption::from_str(matches.get_one::<String>("page_size").unwrap());
+    // `format` is the format given by `Format::from_matches`, and `header` are the header lines, the first of which
+    // names the columns of columnar data
+    pub fn from_matches(matches: &ArgMatches, format: Option<Format>, header: Vec<String>) -> Result<Self, String> {
+        let page_size = PageSizeOption::from_str(matches.get_one::<String>("page_size").unwrap())?;
         let hint_alphabet = matches.get_one::<String>("alphabet").map(String::from);
+        let match_mode = MatchMode::from_str(matches.get_one::<String>("match").unwrap());
+        let theme = Self::parse_theme(matches)?;
+        let ansi = matches.get_flag("ansi");
+        let keep_ansi = matches.get_flag("keep-ansi");
+        let tabstop = match *matches.get_one::<usize>("tabstop").unwrap() {
+            0 => return Err("invalid tabstop '0', expected a positive number".to_string()),
+            n => n,
+        };
+        let pointer = matches.get_one::<String>("pointer").unwrap().to_string();
+        let marker = matches.get_one::<String>("marker").unwrap().to_string();
+        let output_order = OutputOrder::from_str(matches.get_one::<String>("output-order").unwrap());
+        let output_format = matches.get_one::<String>("output-format").map(|f| Template::parse(f));
+        let output_mode = OutputMode::from_str(matches.get_one::<String>("output").unwrap());
+        let query = matches.get_one::<String>("query").cloned();
+        let filter = matches.get_one::<String>("filter").cloned();
+        let scroll = matches.get_flag("scroll");
+        let scroll_off = *matches.get_one::<usize>("scroll-off").unwrap();
+        let preview = matches.get_one::<String>("preview").map(|p| Template::parse(p));
+        let preview_window = PreviewWindow::from_str(matches.get_one::<String>("preview-window").unwrap())?;
+        let bindings = Self::parse_bindings(matches)?;
 
-        let columnar = matches.contains_id("delimiter");
-        if !columnar {
+        let Some(format) = format else {
             return Ok(Self {
                 hint_alphabet: hint_alphabet.unwrap(),
-                page_size: page_size.unwrap(),
-                delimiter: None,
+                page_size,
+                format: None,
                 display_columns: None,
                 output_columns: None,
+                filter_columns: None,
                 selection_regex: None,
+                match_mode: match_mode.unwrap(),
+                theme,
+                ansi,
+                keep_ansi,
+                tabstop,
+                pointer,
+                marker,
+                output_order: output_order.unwrap(),
+                output_format,
+                output_mode: output_mode.unwrap(),
+                query,
+                filter,
+                scroll,
+                scroll_off,
+                preview,
+                preview_window,
+                bindings,
+                header,
+                column_names: Vec::new(),
             })
-        }
-        
-        let delimiter = matches.get_one::<String>("delimiter").unwrap();
+        };
+
+        let column_names = format.column_names(&header);
 
-        let display_columns = matches.get_one::<String>("columns").map(Self::parse_column_ranges);
-        let output_columns = matches.get_one::<String>("output-columns").map(Self::parse_column_ranges);
+        // json lines display the line itself unless told otherwise, while it's output as read without output columns
+        let whole_line = matches!(format, Format::Json(_)).then(|| ".".to_string());
+
+        let parse_columns = |c: &String| Self::parse_column_ranges(c, &column_names);
+        let display_columns = matches.get_one::<String>("columns").or(whole_line.as_ref()).map(parse_columns).transpose()?;
+        let output_columns = matches.get_one::<String>("output-columns").map(parse_columns).transpose()?;
+        let filter_columns = matches.get_one::<String>("filter-columns").map(parse_columns).transpose()?;
         let selection_regex = matches.get_one::<String>("selection-regex").unwrap();
+        let selection_regex = Regex::new(selection_regex)
+            .map_err(|e| format!("invalid selection regex '{}': {}", selection_regex, e))?;
 
         Ok(Self {
             hint_alphabet: hint_alphabet.unwrap(),
-            page_size: page_size.unwrap(),
-            delimiter: Some(delimiter.to_string()),
+            page_size,
+            format: Some(format),
             display_columns,
             output_columns,
-            selection_regex: Some(selection_regex.to_string()),
+            filter_columns,
+            selection_regex: Some(selection_regex),
+            match_mode: match_mode.unwrap(),
+            theme,
+            ansi,
+            keep_ansi,
+            tabstop,
+            pointer,
+            marker,
+            output_order: output_order.unwrap(),
+            output_format,
+            output_mode: output_mode.unwrap(),
+            query,
+            filter,
+            scroll,
+            scroll_off,
+            preview,
+            preview_window,
+            bindings,
+            header,
+            column_names,
         })
     }
 
-    fn parse_column_ranges(columns_list: &String) -> ColumnRange {
+    // --highlight-style is the default style of matches, which can be overridden by --color like any other element
+    fn parse_theme(matches: &ArgMatches) -> Result<Theme, String> {
+        let mut theme = Theme::new(parse_style(matches.get_one::<String>("highlight-style").unwrap())?);
+        for spec in matches.get_many::<String>("color").into_iter().flatten().flat_map(|c| c.split(',')) {
+            theme.set(spec)?;
+        }
+
+        // see https://no-color.org
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color || matches.get_flag("no-color") {
+            theme = theme.without_colors();
+        }
+
+        Ok(theme)
+    }
+
+    // bindings from --bind-file come first, so they can be overridden by --bind
+    fn parse_bindings(matches: &ArgMatches) -> Result<Bindings, String> {
+        let mut bindings = Vec::new();
+
+        if let Some(path) = matches.get_one::<String>("bind-file") {
+            let file = std::fs::read_to_string(path).map_err(|e| format!("unable to read '{}': {}", path, e))?;
+            bindings.extend(file.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(String::from));
+        }
+
+        bindings.extend(matches.get_many::<String>("bind").into_iter().flatten().cloned());
+
+        Bindings::new(&bindings)
+    }
+
+    // columns are given by index or by one of the `names` of the columns
+    pub fn parse_column_ranges(columns_list: &str, names: &[String]) -> Result<ColumnRange, String> {
+        // like field paths, columns can be separated by spaces as well, e.g. `.a, .b` or `0, 2..= 3`
+        let parse_column = |s: &str| {
+            let s = s.trim();
+            s.parse::<usize>().ok()
+                .or_else(|| names.iter().position(|n| !n.is_empty() && n == s))
+                .or_else(|| names.iter().position(|n| !n.is_empty() && n.eq_ignore_ascii_case(s)))
+                .ok_or_else(|| format!("invalid column '{}' in '{}'", s, columns_list))
+        };
+
         let mut columns : Vec<usize> = Vec::new();
-        for s in columns_list.split(',') {
+        for s in columns_list.split(',').map(str::trim) {
             match s {
                 range if s.contains("..") => {
                     let (start, end) = range.split_once("..").unwrap();
 
-                    let start = match start {
+                    let start = match start.trim() {
                         start if start.is_empty() => 0,
-                        _ => start.parse::<usize>().unwrap(),
+                        _ => parse_column(start)?,
                     };
 
-                    if end.is_empty() {
+                    if end.trim().is_empty() {
                         columns.push(start);
-                        return ColumnRange::Open(columns);
+                        return Ok(ColumnRange::Open(columns));
                     }
 
                     if end.contains("=") {
-                        let end = end.replace("=", "").parse::<usize>().unwrap();
+                        let end = parse_column(end.replace("=", "").as_str())?;
                         columns.extend(start..end + 1);
                     } else {
-                        let end = end.parse::<usize>().unwrap();
+                        let end = parse_column(end)?;
                         columns.extend(start..end)
                     }
 
                 },
-                single if s.parse::<usize>().is_ok() => columns.push(single.parse::<usize>().unwrap()),
-                _ => {}
+                single => columns.push(parse_column(single)?),
+            }
+        }
+
+        Ok(ColumnRange::Closed(columns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns<'a>(format: &Format, line: &'a [u8]) -> Vec<&'a [u8]> {
+        format.split_bytes(line).unwrap().into_iter().map(|range| &line[range]).collect()
+    }
+
+    fn delimited(delimiter: Delimiter, max_split: Option<usize>) -> Format {
+        Format::delimited(delimiter, max_split, None).unwrap()
+    }
+
+    #[test]
+    fn split_bytes_on_a_literal() {
+        let format = delimited(Delimiter::Literal(",".to_string()), None);
+        assert_eq!(columns(&format, b"a,\xff,,b"), vec![&b"a"[..], b"\xff", b"", b"b"]);
+        assert_eq!(columns(&format, b""), vec![&b""[..]]);
+        assert_eq!(columns(&format, b","), vec![&b""[..], b""]);
+
+        let format = delimited(Delimiter::Literal(".".to_string()), None);
+        assert_eq!(columns(&format, b"a.b"), vec![&b"a"[..], b"b"]);
+    }
+
+    #[test]
+    fn split_bytes_on_a_regex() {
+        let format = delimited(Delimiter::Regex(Regex::new("[0-9]+").unwrap()), None);
+        assert_eq!(columns(&format, b"a12\xffb3c"), vec![&b"a"[..], b"\xffb", b"c"]);
+    }
+
+    #[test]
+    fn split_bytes_on_whitespace() {
+        let format = delimited(Delimiter::Whitespace, None);
+        assert_eq!(columns(&format, b"  a \t\xff  b  "), vec![&b"a"[..], b"\xff", b"b"]);
+        assert_eq!(columns(&format, b"a"), vec![&b"a"[..]]);
+        assert_eq!(columns(&format, b"   "), vec![&b""[..]]);
+        assert_eq!(columns(&format, b""), vec![&b""[..]]);
+    }
+
+    #[test]
+    fn split_bytes_at_most_max_split_times() {
+        let format = delimited(Delimiter::Whitespace, Some(1));
+        assert_eq!(columns(&format, b" a  b \xff c "), vec![&b"a"[..], b"b \xff c"]);
+
+        let format = delimited(Delimiter::Literal(",".to_string()), Some(2));
+        assert_eq!(columns(&format, b"a,b,c,\xff"), vec![&b"a"[..], b"b", b"c,\xff"]);
+
+        let format = delimited(Delimiter::Literal(",".to_string()), Some(0));
+        assert_eq!(columns(&format, b"a,b"), vec![&b"a,b"[..]]);
+    }
+
+    #[test]
+    fn split_bytes_agrees_with_split() {
+        let lines = ["  a   b  c ", "a", "", "a b", " x\ty  z"];
+        for max_split in [None, Some(0), Some(1), Some(5)] {
+            let format = delimited(Delimiter::Whitespace, max_split);
+            for line in lines {
+                let split = format.split(line);
+                assert_eq!(columns(&format, line.as_bytes()), split.iter().map(|c| c.as_bytes()).collect::<Vec<_>>());
             }
         }
+    }
+
+    #[test]
+    fn column_ranges_ignore_spaces() {
+        let names = [".a".to_string(), ".b".to_string(), "name".to_string()];
+        let ColumnRange::Closed(columns) = Options::parse_column_ranges(".a, .b", &names).unwrap() else { panic!() };
+        assert_eq!(columns, vec![0, 1]);
+        let ColumnRange::Closed(columns) = Options::parse_column_ranges(" 0 , 1..=name ", &names).unwrap() else { panic!() };
+        assert_eq!(columns, vec![0, 1, 2]);
+        let ColumnRange::Open(columns) = Options::parse_column_ranges("1, 3.. ", &names).unwrap() else { panic!() };
+        assert_eq!(columns, vec![1, 3]);
+        assert!(Options::parse_column_ranges("0, x", &names).is_err());
+    }
 
-        ColumnRange::Closed(columns)
+    #[test]
+    fn only_delimited_lines_are_split_as_bytes() {
+        assert!(Format::Csv(',').split_bytes(b"a,b").is_none());
+        assert!(Format::Json(vec![".".to_string()]).split_bytes(b"{}").is_none());
     }
-}
\ No newline at end of file
+}
@@ -1,22 +1,68 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::HashSet;
-use crate::picker::options::{ColumnRange, Options};
+use std::ops::Range;
+use crate::picker::ansi;
+use crate::picker::ansi::Styles;
+use crate::picker::jsonl;
+use crate::picker::matcher::MatchMode;
+use crate::picker::options::{ColumnRange, Format, Options, OutputMode, OutputOrder};
+use crate::picker::query::Query;
 use regex::Regex;
+use serde_json::{json, Value};
 
 // todo: consider having two different types of lines, representing simple and columnar data
 pub struct Line {
     data: Vec<String>,
     original: String,
+    // the parsed line of json lines input, which json output emits as is
+    object: Option<Value>,
+    // the colors of each column with --ansi, the text itself is kept without escape sequences
+    styles: Vec<Styles>,
+    // the line as it was read when it isn't valid utf-8, in which case the text has replacement characters instead
+    bytes: Option<Vec<u8>>,
+    // the columns of such a line as ranges of its bytes, when it could be split on them
+    ranges: Vec<Range<usize>>,
+    // the line as it was read when --ansi removed escape sequences from it, which --keep-ansi outputs as is
+    escaped: Option<Vec<u8>>,
 }
 
 impl Line {
-    fn new(text: &String, delimiter: Option<String>) -> Self {
-        let Some(delim) = delimiter else {
-            return Self { data: vec![text.to_string()], original: text.to_string() };
+    pub fn new(bytes: &[u8], format: &Option<Format>, ansi: bool) -> Self {
+        let (text, bytes) = match String::from_utf8_lossy(bytes) {
+            Cow::Borrowed(text) => (text.to_string(), None),
+            Cow::Owned(text) => (text, Some(bytes.to_vec())),
         };
 
-        let data = text.split(delim.as_str()).map(|s| s.to_string()).collect::<Vec<String>>();
+        // the text is what's output with --ansi, as it has escape sequences removed
+        let (text, styles, bytes, escaped) = match ansi {
+            true => match ansi::parse(&text) {
+                (plain, _) if plain == text => (text, Vec::new(), bytes, None),
+                (plain, styles) => (plain, styles, None, Some(bytes.unwrap_or_else(|| text.into_bytes()))),
+            },
+            false => (text, Vec::new(), bytes, None),
+        };
+
+        let ranges = match (&bytes, format) {
+            (Some(bytes), Some(format)) => format.split_bytes(bytes).unwrap_or_default(),
+            _ => Vec::new(),
+        };
+
+        let (data, object) = match (format, &bytes) {
+            (None, _) => (vec![text.clone()], None),
+            (Some(Format::Json(paths)), _) => {
+                let object = jsonl::parse(&text);
+                (jsonl::fields(&object, paths, &text), Some(object))
+            },
+            (Some(_), Some(bytes)) if !ranges.is_empty() => {
+                (ranges.iter().map(|r| String::from_utf8_lossy(&bytes[r.clone()]).into_owned()).collect(), None)
+            },
+            (Some(format), _) => (format.split(&text), None),
+        };
+
+        let styles = ansi::columns(&text, &data, &styles);
 
-        Self { data, original: text.to_string() }
+        Self { data, original: text, object, styles, bytes, ranges, escaped }
     }
 
     // todo: maybe this and the `output` method belongs in ui.rs
@@ -27,18 +73,102 @@ impl Line {
         }
     }
 
-    // todo: fix issue where delimiter is cloned into this everywhere..
-    pub fn output(&self, columns: &Option<ColumnRange>, delimiter: Option<String>) -> String {
-        let cols = match columns {
-            None => self.data.clone(),
-            Some(range) => Self::filter_columns(&self.data, &range),
+    // the styles of each displayed column
+    pub fn display_styles(&self, columns: &Option<ColumnRange>) -> Vec<Styles> {
+        self.styles.iter()
+            .enumerate()
+            .filter(|(i, _)| columns.as_ref().is_none_or(|range| range.contains(*i)))
+            .map(|(_, s)| s.clone())
+            .collect()
+    }
+
+    // `keep_ansi` outputs the columns with their colors, as they were given with --ansi. without output columns, the
+    // line is output as it was read, even if it isn't valid utf-8, and with all of its escape sequences with `keep_ansi`
+    pub fn output(&self, columns: &Option<ColumnRange>, format: &Option<Format>, keep_ansi: bool) -> Vec<u8> {
+        let Some(columns) = columns else {
+            return match (keep_ansi, &self.escaped) {
+                (true, Some(escaped)) => escaped.clone(),
+                _ => self.original_bytes().to_vec(),
+            };
+        };
+
+        if let (false, Some(Format::Delimited { output, .. })) = (self.ranges.is_empty(), format) {
+            let cols = (0..self.ranges.len())
+                .filter(|i| columns.contains(*i))
+                .filter_map(|i| self.column_bytes(i))
+                .collect::<Vec<&[u8]>>();
+
+            return cols.join(output.as_bytes());
+        }
+
+        let data = match keep_ansi && !self.styles.is_empty() {
+            true => self.data.iter().zip(&self.styles).map(|(col, styles)| ansi::render(col, styles)).collect(),
+            false => self.data.clone(),
         };
 
-        cols.join(delimiter.unwrap_or_default().as_str())
+        let cols = Self::filter_columns(&data, columns);
+
+        match format {
+            Some(format) => format.join(&cols).into_bytes(),
+            None => cols.concat().into_bytes(),
+        }
+    }
+
+    pub fn column(&self, index: usize) -> Option<&str> {
+        self.data.get(index).map(String::as_str)
+    }
+
+    // the column as it was read, which differs from `column` for lines that aren't valid utf-8
+    pub fn column_bytes(&self, index: usize) -> Option<&[u8]> {
+        match (&self.bytes, self.ranges.get(index)) {
+            (Some(bytes), Some(range)) => Some(&bytes[range.clone()]),
+            _ => self.column(index).map(str::as_bytes),
+        }
     }
 
-    pub fn matches(&self, s: &String) -> bool {
-        self.data.iter().any(|x| x.contains(s))
+    // the line as it was read
+    pub fn original_bytes(&self) -> &[u8] {
+        self.bytes.as_deref().unwrap_or(self.original.as_bytes())
+    }
+
+    // `names` are the names of the columns, which add the columns by name as "fields" when given
+    // json lines input is output as the object it was
+    pub fn json(&self, index: usize, names: &[String]) -> Value {
+        if let Some(object) = &self.object {
+            return object.clone();
+        }
+
+        let mut json = json!({
+            "index": index,
+            "text": self.original,
+            "columns": self.data,
+        });
+
+        if !names.is_empty() {
+            let fields = names.iter().zip(&self.data).filter(|(n, _)| !n.is_empty()).map(|(n, c)| (n.to_string(), Value::from(c.as_str()))).collect();
+            json["fields"] = Value::Object(fields);
+        }
+
+        json
+    }
+
+    pub fn score(&self, query: &Query) -> Option<i64> {
+        query.find(&self.data).map(|m| m.score)
+    }
+
+    // the indexes of the characters matching the query, for each displayed column
+    pub fn highlights(&self, query: &Query, columns: &Option<ColumnRange>) -> Vec<Vec<usize>> {
+        let positions = query.find(&self.data).map(|m| m.positions).unwrap_or_default();
+
+        (0..self.data.len())
+            .filter(|i| columns.as_ref().is_none_or(|range| range.contains(*i)))
+            .map(|col| {
+                let mut chars = positions.iter().filter(|(c, _)| *c == col).map(|(_, i)| *i).collect::<Vec<usize>>();
+                chars.sort();
+                chars.dedup();
+                chars
+            })
+            .collect()
     }
 
     pub fn matches_regex(&self, regex: &Regex) -> bool {
@@ -47,68 +177,88 @@ impl Line {
 
     // todo: maybe try to avoid cloning this much
     fn filter_columns(data: &[String], columns: &ColumnRange) -> Vec<String> {
-        match columns {
-            ColumnRange::Closed(range) => {
-                data.iter()
-                    .enumerate()
-                    .filter_map(|(i, s)| range.contains(&i).then(|| s.to_string()))
-                    .collect()
-            },
-            ColumnRange::Open(range) => {
-                if range.is_empty() {
-                    return Vec::new();
-                }
-
-                let &last = range.last().unwrap();
-
-                data.iter()
-                    .enumerate()
-                    .filter_map(|(i, s)| {
-                        if i > last || range.contains(&i) {
-                            Some(s.to_string())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            }
-        }
+        data.iter()
+            .enumerate()
+            .filter(|(i, _)| columns.contains(*i))
+            .map(|(_, s)| s.to_string())
+            .collect()
     }
 }
 pub struct Picker {
     lines: Vec<Line>,
     filter: Option<String>,
-    selection: HashSet<usize>,
+    query: Option<Query>,
+    match_mode: MatchMode,
+    // the selected lines in the order they were selected, along with a set for quick lookups
+    selection: Vec<usize>,
+    selected: HashSet<usize>,
     opts: Options,
 }
 
 impl Picker {
-    pub fn new(lines: &[String], opts: Options) -> Self {
-        let lines = lines.iter().map(|l| Line::new(l, opts.delimiter.clone())).collect::<Vec<Line>>();
-
+    pub fn new(opts: Options) -> Self {
         Self {
-            lines,
+            lines: Vec::new(),
             filter: None,
-            selection: HashSet::new(),
+            query: None,
+            match_mode: opts.match_mode,
+            selection: Vec::new(),
+            selected: HashSet::new(),
             opts,
         }
     }
 
-    pub fn result(&self) -> Option<Vec<String>> {
-        match &self.selection {
-            s if s.len() > 0 => {
-                let selected = s.iter().map(|i| {
-                    self.lines.get(*i).unwrap().output(&self.opts.output_columns, self.opts.delimiter.clone())
-                });
+    // returns the indexes of the added lines
+    pub fn push_lines(&mut self, lines: &[Vec<u8>]) -> Range<usize> {
+        let start = self.lines.len();
+        self.lines.extend(lines.iter().map(|l| Line::new(l, &self.opts.format, self.opts.ansi)));
 
-                Some(selected.collect())
+        start..self.lines.len()
+    }
+
+    pub fn result(&self) -> Option<Vec<Vec<u8>>> {
+        self.result_for(self.ordered_selection().as_slice())
+    }
+
+    // the output for the given lines, or None if there are no lines
+    pub fn result_for(&self, indexes: &[usize]) -> Option<Vec<Vec<u8>>> {
+        if indexes.is_empty() {
+            return None;
+        }
+
+        match self.opts.output_mode {
+            OutputMode::Json => {
+                let lines = indexes.iter().map(|i| self.lines.get(*i).unwrap().json(*i, &self.opts.column_names)).collect();
+                Some(vec![Value::Array(lines).to_string().into_bytes()])
             },
-            _ => None,
+            _ => Some(indexes.iter().map(|i| self.output(*i)).collect()),
         }
     }
 
-    pub fn selected(&self) -> Vec<&Line> {
-        self.selection.iter().map(|i| self.lines.get(*i).unwrap()).collect::<Vec<&Line>>()
+    // the output of each selected line, even when the result is a single json array
+    pub fn selected(&self) -> Vec<String> {
+        self.ordered_selection().iter().map(|i| String::from_utf8_lossy(&self.output(*i)).into_owned()).collect()
+    }
+
+    fn output(&self, index: usize) -> Vec<u8> {
+        let line = self.lines.get(index).unwrap();
+
+        match (self.opts.output_mode, &self.opts.output_format) {
+            (OutputMode::Json | OutputMode::JsonLines, _) => line.json(index, &self.opts.column_names).to_string().into_bytes(),
+            (OutputMode::Text, Some(template)) => template.render(line, index, self.filter_text().as_str()),
+            (OutputMode::Text, None) => line.output(&self.opts.output_columns, &self.opts.format, self.opts.keep_ansi),
+        }
+    }
+
+    fn ordered_selection(&self) -> Vec<usize> {
+        let mut selection = self.selection.clone();
+        match self.opts.output_order {
+            OutputOrder::Input => selection.sort(),
+            OutputOrder::Selection => {},
+            OutputOrder::Reverse => selection.reverse(),
+        }
+
+        selection
     }
 
     pub fn lines(&self) -> &[Line] {
@@ -116,14 +266,27 @@ impl Picker {
     }
 
     pub fn toggle_selection(&mut self, index: usize) {
-        let _ = match self.selection.contains(&index) {
-            false => self.selection.insert(index),
-            true => self.selection.remove(&index),
-        };
+        match self.selected.insert(index) {
+            true => self.selection.push(index),
+            false => {
+                self.selected.remove(&index);
+                self.selection.retain(|i| *i != index);
+            },
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if self.selected.insert(index) {
+            self.selection.push(index);
+        }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        !self.selection.is_empty()
     }
 
     pub fn is_selected(&self, index: usize) -> bool {
-        self.selection.contains(&index)
+        self.selected.contains(&index)
     }
 
     pub fn filter_text(&self) -> String {
@@ -134,22 +297,147 @@ impl Picker {
         self.filter = Some(filter);
     }
 
-    // todo: look into not cloning here..
+    pub fn match_mode(&self) -> MatchMode {
+        self.match_mode
+    }
+
+    pub fn cycle_match_mode(&mut self) {
+        self.match_mode = self.match_mode.next();
+    }
+
     pub fn apply_filter(&mut self, filter: String) -> Vec<usize> {
-        let indexes = match &filter {
-            f if f.len() == 0 => {
-                (0..self.lines.len()).collect()
-            },
-            f => {
-                self.lines.iter().enumerate().filter_map(|(i, l)| {
-                    match l.matches(f) {
-                        true => Some(i),
-                        false => None,
-                    }
-                }).collect::<Vec<usize>>()
-            }
+        let query = Query::parse(filter.as_str(), self.match_mode, &self.opts.filter_columns, &self.opts.column_names, self.opts.format.is_some());
+        if query.is_empty() {
+            self.query = None;
+            return (0..self.lines.len()).collect();
+        }
+
+        let mut scored = self.lines.iter().enumerate().filter_map(|(i, l)| {
+            l.score(&query).map(|score| (i, score))
+        }).collect::<Vec<(usize, i64)>>();
+
+        // only fuzzy matches are ranked, the stable sort keeps input order for equal scores
+        if self.match_mode == MatchMode::Fuzzy {
+            scored.sort_by_key(|(_, score)| Reverse(*score));
+        }
+
+        self.query = Some(query);
+        scored.into_iter().map(|(i, _)| i).collect()
+    }
+
+    // merges the added lines matching the current filter into the visible lines, in the order apply_filter would have
+    // put them in, without filtering the lines that were already there again
+    pub fn merge_filter(&self, added: Range<usize>, visible: &mut Vec<usize>) {
+        let Some(query) = &self.query else {
+            visible.extend(added);
+            return;
         };
 
-        indexes.clone()
+        let mut scored = added.filter_map(|i| {
+            self.lines[i].score(query).map(|score| (i, score))
+        }).collect::<Vec<(usize, i64)>>();
+
+        if self.match_mode != MatchMode::Fuzzy {
+            visible.extend(scored.into_iter().map(|(i, _)| i));
+            return;
+        }
+
+        // the added lines come after all others in the input, so each one goes after the lines scoring at least as high
+        scored.sort_by_key(|(_, score)| Reverse(*score));
+        let score = |i: usize| self.lines[i].score(query).unwrap_or_default();
+        let positions = scored.iter()
+            .map(|(_, s)| visible.partition_point(|i| score(*i) >= *s))
+            .collect::<Vec<usize>>();
+
+        // move the lines after each insert position back from the end, so every line is moved at most once
+        let mut end = visible.len();
+        visible.resize(end + scored.len(), 0);
+        for (n, ((i, _), position)) in scored.into_iter().zip(positions).enumerate().rev() {
+            visible.copy_within(position..end, position + n + 1);
+            visible[position + n] = i;
+            end = position;
+        }
+    }
+
+    // the indexes of the characters matching the current filter, for each displayed column of the line
+    pub fn highlights(&self, index: usize) -> Vec<Vec<usize>> {
+        match (&self.query, self.lines.get(index)) {
+            (Some(query), Some(line)) => line.highlights(query, &self.opts.display_columns),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picker::options::Delimiter;
+
+    fn picker(args: &[&str]) -> Picker {
+        let matches = crate::command().get_matches_from(std::iter::once("pickline").chain(args.iter().copied()));
+        let format = Format::from_matches(&matches).unwrap();
+        Picker::new(Options::from_matches(&matches, format, Vec::new()).unwrap())
+    }
+
+    fn delimited(delimiter: Delimiter) -> Option<Format> {
+        Some(Format::delimited(delimiter, None, None).unwrap())
+    }
+
+    fn output(line: &[u8], format: Option<Format>, columns: Option<ColumnRange>) -> Vec<u8> {
+        Line::new(line, &format, false).output(&columns, &format, false)
+    }
+
+    #[test]
+    fn whole_delimited_lines_are_output_as_read() {
+        assert_eq!(output(b"  a   b  c", delimited(Delimiter::Whitespace), None), b"  a   b  c");
+        assert_eq!(output(b"a1b2c", delimited(Delimiter::Regex(Regex::new("[0-9]").unwrap())), None), b"a1b2c");
+        assert_eq!(output(b"a,,b", delimited(Delimiter::Literal(",".to_string())), None), b"a,,b");
+    }
+
+    #[test]
+    fn whole_csv_records_are_output_as_read() {
+        assert_eq!(output(b"\"a\",b", Some(Format::Csv(',')), None), b"\"a\",b");
+        assert_eq!(output(b"a,\"b\nc\"\"\"", Some(Format::Csv(',')), None), b"a,\"b\nc\"\"\"");
+    }
+
+    #[test]
+    fn csv_output_columns_are_quoted_where_needed() {
+        let columns = Some(ColumnRange::Closed(vec![0, 1]));
+        assert_eq!(output(b"\"a\",\"b;c\",d", Some(Format::Csv(',')), columns.clone()), b"a,b;c");
+        assert_eq!(output(b"\"a,b\",\"c\"\"\"", Some(Format::Csv(',')), columns), b"\"a,b\",\"c\"\"\"");
+    }
+
+    #[test]
+    fn whole_lines_that_arent_utf8_are_output_as_read() {
+        assert_eq!(output(b"a,\xff", Some(Format::Csv(',')), None), b"a,\xff");
+        assert_eq!(output(b"{\"a\": \"\xff\"}", Some(Format::Json(vec![".".to_string()])), None), b"{\"a\": \"\xff\"}");
+        assert_eq!(output(b"a \xff", delimited(Delimiter::Whitespace), None), b"a \xff");
+        assert_eq!(output(b"a \xff", None, None), b"a \xff");
+    }
+
+    #[test]
+    fn output_columns_are_joined() {
+        let columns = Some(ColumnRange::Closed(vec![0, 2]));
+        assert_eq!(output(b"  a   b  c", delimited(Delimiter::Whitespace), columns.clone()), b"a c");
+        assert_eq!(output(b"a1b2c", delimited(Delimiter::Regex(Regex::new("[0-9]").unwrap())), columns.clone()), b"a c");
+        assert_eq!(output(b"a,b,c", delimited(Delimiter::Literal(",".to_string())), columns), b"a,c");
+    }
+
+    #[test]
+    fn merging_added_lines_gives_the_order_of_filtering_all_lines() {
+        let batches: [&[&str]; 4] = [&["xaxb", "zz", "a b"], &[], &["ab", "a-b", "xab", "b a"], &["aab", "ab", "zab"]];
+        let cases = [("fuzzy", "ab"), ("fuzzy", "a b"), ("fuzzy", "!z ab"), ("fuzzy", ""), ("exact", "ab"), ("regex", "a.?b")];
+
+        for (mode, filter) in cases {
+            let mut picker = picker(&["--match", mode]);
+            let mut visible = picker.apply_filter(filter.to_string());
+
+            for batch in batches {
+                let added = picker.push_lines(&batch.iter().map(|l| l.as_bytes().to_vec()).collect::<Vec<_>>());
+                picker.merge_filter(added, &mut visible);
+            }
+
+            assert_eq!(visible, picker.apply_filter(filter.to_string()), "{} {}", mode, filter);
+        }
     }
 }
@@ -0,0 +1,158 @@
+use std::ffi::OsStr;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+
+use crate::picker::picker::Picker;
+use crate::picker::template::Template;
+
+// the number of lines of output that are kept, after which the command is stopped
+const MAX_LINES: usize = 10_000;
+
+// runs the preview command for the line under the cursor in the background, the command is
+// killed when the cursor moves to another line before it finishes
+pub struct Preview {
+    template: Template,
+    line: Option<usize>,
+    child: Option<Child>,
+    rx: Option<Receiver<String>>,
+    output: Vec<String>,
+    scroll: usize,
+}
+
+impl Preview {
+    pub fn new(template: Template) -> Self {
+        Self {
+            template,
+            line: None,
+            child: None,
+            rx: None,
+            output: Vec::new(),
+            scroll: 0,
+        }
+    }
+
+    // starts previewing the given line, unless it is already being previewed
+    pub fn show(&mut self, index: Option<usize>, picker: &Picker) {
+        if index == self.line {
+            return;
+        }
+
+        self.cancel();
+        self.line = index;
+        self.output.clear();
+        self.scroll = 0;
+
+        let Some(index) = index else {
+            return;
+        };
+
+        let command = self.template.render_quoted(&picker.lines()[index], index, &picker.filter_text());
+        if let Err(e) = self.spawn(command) {
+            self.output = vec![format!("unable to run preview: {}", e)];
+        }
+    }
+
+    fn spawn(&mut self, command: Vec<u8>) -> io::Result<()> {
+        // stdout and stderr share a pipe, so errors are shown in the preview as well
+        let (reader, writer) = io::pipe()?;
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(OsStr::from_bytes(&command))
+            .stdin(Stdio::null())
+            .stdout(writer.try_clone()?)
+            .stderr(writer)
+            .process_group(0)
+            .spawn()?;
+
+        // lines are passed on as they are read, so the output of slow commands is shown as it comes
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).split(b'\n').take(MAX_LINES) {
+                let Ok(mut line) = line else {
+                    break;
+                };
+
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+
+                if tx.send(String::from_utf8_lossy(&line).into_owned()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.child = Some(child);
+        self.rx = Some(rx);
+
+        Ok(())
+    }
+
+    // picks up the lines the command has output so far, returning true if there is something new to draw
+    pub fn poll(&mut self) -> bool {
+        let Some(rx) = &self.rx else {
+            return false;
+        };
+
+        let mut changed = false;
+        loop {
+            match rx.try_recv() {
+                Ok(line) => {
+                    self.output.push(line);
+                    changed = true;
+                },
+                Err(TryRecvError::Empty) => return changed,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        // the output has ended, or has more lines than are kept, in which case the command has to be stopped
+        self.cancel();
+
+        true
+    }
+
+    pub fn running(&self) -> bool {
+        self.rx.is_some()
+    }
+
+    fn cancel(&mut self) {
+        self.rx = None;
+        if let Some(mut child) = self.child.take() {
+            // the command runs in its own process group, so anything it started is killed along with it
+            // safety: kill has no memory safety requirements
+            unsafe {
+                libc::kill(-(child.id() as i32), libc::SIGKILL);
+            }
+
+            let _ = child.wait();
+        }
+    }
+
+    // the lines to show, starting at the current scroll position
+    pub fn lines(&self) -> &[String] {
+        &self.output[self.scroll..]
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.scroll + 1 < self.output.len() {
+            self.scroll += 1;
+        }
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,299 @@
+use crate::picker::matcher::{Match, MatchKind, MatchMode, Matcher};
+use crate::picker::options::{ColumnRange, Options};
+
+// a parsed filter, e.g. `api !test ^prod | ^stage`
+//
+// terms separated by spaces must all match, while terms separated by `|` match if either does.
+// `!` negates a term, `^` and `$` anchor it to the start or end of a column and `'` toggles
+// between exact and fuzzy matching. a term can be limited to some columns by prefixing it with
+// a list of columns, e.g. `2:nginx` or `0..2:nginx`, when the input is columnar. a literal space can be written as `\ `.
+pub struct Query {
+    groups: Vec<Vec<Term>>,
+}
+
+struct Term {
+    matcher: Matcher,
+    negated: bool,
+    columns: Option<ColumnRange>,
+    // the term as it was written, for lines without any of the columns of a `2:nginx` style term
+    fallback: Option<Box<Term>>,
+}
+
+impl Term {
+    fn find(&self, cols: &[(usize, &str)]) -> Option<Match> {
+        if let (Some(range), Some(fallback)) = (&self.columns, &self.fallback) && !cols.iter().any(|(i, _)| range.contains(*i)) {
+            return fallback.find(cols);
+        }
+
+        let found = match &self.columns {
+            None => self.matcher.find(cols),
+            Some(range) => {
+                let scoped = cols.iter().filter(|(i, _)| range.contains(*i)).copied().collect::<Vec<(usize, &str)>>();
+                self.matcher.find(scoped.as_slice())
+            },
+        };
+
+        match (found, self.negated) {
+            (Some(found), false) => Some(found),
+            (None, true) => Some(Match { score: 0, positions: Vec::new() }),
+            _ => None,
+        }
+    }
+}
+
+impl Query {
+    // terms without columns of their own are matched against `columns`, or every column if None
+    // `names` are the names of the columns, which can be used instead of their index
+    // terms are only limited to columns of their own when the input is `columnar`
+    pub fn parse(input: &str, mode: MatchMode, columns: &Option<ColumnRange>, names: &[String], columnar: bool) -> Self {
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut or_pending = false;
+
+        for token in Self::tokenize(input) {
+            if token == "|" {
+                or_pending = !groups.is_empty();
+                continue;
+            }
+
+            let Some(term) = Self::parse_term(token.as_str(), mode, columns, names, columnar) else {
+                continue;
+            };
+
+            match groups.last_mut() {
+                Some(group) if or_pending => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+
+            or_pending = false;
+        }
+
+        Self { groups }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    // every group has to match, the score is the sum of the best scoring term in each group
+    // while the positions of every matching term are kept for highlighting
+    pub fn find(&self, cols: &[String]) -> Option<Match> {
+        let cols = cols.iter().map(String::as_str).enumerate().collect::<Vec<(usize, &str)>>();
+
+        let mut result = Match { score: 0, positions: Vec::new() };
+        for group in &self.groups {
+            let found = group.iter().filter_map(|t| t.find(cols.as_slice())).collect::<Vec<Match>>();
+
+            result.score += found.iter().map(|m| m.score).max()?;
+            result.positions.extend(found.into_iter().flat_map(|m| m.positions));
+        }
+
+        Some(result)
+    }
+
+    fn tokenize(input: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut token = String::new();
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => {
+                    token.push(' ');
+                    chars.next();
+                },
+                ' ' => {
+                    if !token.is_empty() {
+                        tokens.push(std::mem::take(&mut token));
+                    }
+                },
+                c => token.push(c),
+            }
+        }
+
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+
+        tokens
+    }
+
+    fn parse_term(token: &str, mode: MatchMode, columns: &Option<ColumnRange>, names: &[String], columnar: bool) -> Option<Term> {
+        let (negated, token) = match token.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+
+        let unscoped = || Self::matcher(token, mode, negated).map(|matcher| Term { matcher, negated, columns: columns.clone(), fallback: None });
+
+        match Self::parse_columns(token, names).filter(|_| columnar) {
+            Some((range, rest)) => {
+                let matcher = Self::matcher(rest, mode, negated)?;
+                Some(Term { matcher, negated, columns: Some(range), fallback: unscoped().map(Box::new) })
+            },
+            None => unscoped(),
+        }
+    }
+
+    fn matcher(token: &str, mode: MatchMode, negated: bool) -> Option<Matcher> {
+        let (kind, text) = if let Some(rest) = token.strip_prefix('\'') {
+            match mode {
+                MatchMode::Exact => (MatchKind::Fuzzy, rest),
+                _ => (MatchKind::Exact, rest),
+            }
+        } else if mode == MatchMode::Regex {
+            (MatchKind::Regex, token)
+        } else if let Some(rest) = token.strip_prefix('^') {
+            match rest.strip_suffix('$') {
+                Some(rest) => (MatchKind::Equal, rest),
+                None => (MatchKind::Prefix, rest),
+            }
+        } else if let Some(rest) = token.strip_suffix('$').filter(|r| !r.is_empty()) {
+            (MatchKind::Suffix, rest)
+        } else if negated || mode == MatchMode::Exact {
+            // a negated fuzzy term would exclude almost everything, so they are matched exactly
+            (MatchKind::Exact, token)
+        } else {
+            (MatchKind::Fuzzy, token)
+        };
+
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(Matcher::new(kind, text))
+    }
+
+    // splits a `2:nginx` or `name:nginx` style term into its columns and the rest of the term
+    fn parse_columns<'a>(token: &'a str, names: &[String]) -> Option<(ColumnRange, &'a str)> {
+        let (columns, rest) = token.split_once(':')?;
+
+        Options::parse_column_ranges(columns, names).ok().map(|range| (range, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(query: &str, mode: MatchMode, line: &str) -> Option<Match> {
+        Query::parse(query, mode, &None, &[], false).find(&[line.to_string()])
+    }
+
+    fn matches(query: &str, line: &str) -> bool {
+        find(query, MatchMode::Fuzzy, line).is_some()
+    }
+
+    #[test]
+    fn tokenize() {
+        assert_eq!(Query::tokenize("  api  test "), vec!["api", "test"]);
+        assert_eq!(Query::tokenize("a\\ b c"), vec!["a b", "c"]);
+        assert_eq!(Query::tokenize("a\\b"), vec!["a\\b"]);
+        assert_eq!(Query::tokenize("a | b"), vec!["a", "|", "b"]);
+        assert!(Query::tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn escaped_space() {
+        assert!(matches("'a\\ b", "xa by"));
+        assert!(!matches("'a\\ b", "ab a"));
+    }
+
+    #[test]
+    fn and() {
+        assert!(matches("api test", "test the api"));
+        assert!(!matches("api test", "the api"));
+    }
+
+    #[test]
+    fn or() {
+        assert!(matches("api | web", "web server"));
+        assert!(matches("api | web test", "api test"));
+        assert!(!matches("api | web test", "web server"));
+        assert!(!matches("xyz | web", "api"));
+    }
+
+    #[test]
+    fn leading_or() {
+        let query = Query::parse("| api", MatchMode::Fuzzy, &None, &[], false);
+        assert_eq!(query.groups.len(), 1);
+        assert!(matches("| api", "api"));
+        assert!(!matches("| api", "web"));
+        assert!(matches("api | | web", "web"));
+    }
+
+    #[test]
+    fn negation() {
+        assert!(matches("!test", "api"));
+        assert!(!matches("!test", "api test"));
+        // negated terms are exact, a fuzzy match doesn't exclude the line
+        assert!(matches("!test", "t-e-s-t"));
+        assert!(matches("api !test", "api"));
+        assert!(!matches("api !test", "api test"));
+        assert!(Query::parse("!", MatchMode::Fuzzy, &None, &[], false).is_empty());
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("^api", "api server"));
+        assert!(!matches("^api", "the api"));
+        assert!(matches("api$", "the api"));
+        assert!(!matches("api$", "api server"));
+        assert!(matches("^api$", "api"));
+        assert!(!matches("^api$", "api server"));
+        assert!(matches("!^api", "the api"));
+    }
+
+    #[test]
+    fn exact_toggle() {
+        assert!(matches("'api", "the api"));
+        assert!(!matches("'api", "a-p-i"));
+        assert!(find("'api", MatchMode::Exact, "a-p-i").is_some());
+        assert!(find("api", MatchMode::Exact, "a-p-i").is_none());
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(matches("api", "API"));
+        assert!(!matches("Api", "api"));
+        assert!(matches("Api", "Api"));
+    }
+
+    #[test]
+    fn regex() {
+        assert!(find("^a.i$", MatchMode::Regex, "api").is_some());
+        assert!(find("^a.i$", MatchMode::Regex, "apis").is_none());
+    }
+
+    #[test]
+    fn scores_best_term_of_each_group() {
+        let both = find("ab | xyz", MatchMode::Fuzzy, "ab xyz").unwrap();
+        let one = find("ab", MatchMode::Fuzzy, "ab xyz").unwrap();
+        let other = find("xyz", MatchMode::Fuzzy, "ab xyz").unwrap();
+        assert_eq!(both.score, one.score.max(other.score));
+        assert_eq!(both.positions.len(), 5);
+    }
+
+    #[test]
+    fn highlight_positions() {
+        let found = find("^API", MatchMode::Fuzzy, "API api").unwrap();
+        assert_eq!(found.positions, vec![(0, 0), (0, 1), (0, 2)]);
+
+        let found = find("'bul", MatchMode::Fuzzy, "İSTANBUL").unwrap();
+        assert_eq!(found.positions, vec![(0, 5), (0, 6), (0, 7)]);
+    }
+
+    #[test]
+    fn column_scopes() {
+        let cols = ["api".to_string(), "web".to_string()];
+        let query = Query::parse("1:api", MatchMode::Fuzzy, &None, &[], true);
+        assert!(query.find(&cols).is_none());
+        let query = Query::parse("0:api", MatchMode::Fuzzy, &None, &[], true);
+        assert!(query.find(&cols).is_some());
+
+        // past the last column, and for input that isn't columnar, the term is matched as written
+        let query = Query::parse("5:api", MatchMode::Fuzzy, &None, &[], true);
+        assert!(query.find(&cols).is_none());
+        assert!(query.find(&["5:api".to_string()]).is_some());
+        assert!(matches("10:30", "at 10:30"));
+    }
+}
@@ -0,0 +1,144 @@
+use crossterm::style::{Attribute, Color, ContentStyle, Stylize};
+use std::str::FromStr;
+
+// parses styles like `red+bold` or `#ff8700+underline+bg-black`, colors can be given by name,
+// as an ansi value (0-255) or as a hex value, and are used as foreground unless prefixed by `bg-`
+pub fn parse_style(spec: &str) -> Result<ContentStyle, String> {
+    let mut style = ContentStyle::new();
+
+    for part in spec.split('+').filter(|p| !p.is_empty()) {
+        match part.to_lowercase().as_str() {
+            "bold" => style.attributes.set(Attribute::Bold),
+            "dim" => style.attributes.set(Attribute::Dim),
+            "italic" => style.attributes.set(Attribute::Italic),
+            "underline" => style.attributes.set(Attribute::Underlined),
+            "reverse" => style.attributes.set(Attribute::Reverse),
+            part => match part.strip_prefix("bg-") {
+                Some(color) => style.background_color = Some(parse_color(color)?),
+                None => style.foreground_color = Some(parse_color(part)?),
+            },
+        }
+    }
+
+    Ok(style)
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb { r, g, b }),
+            _ => Err(format!("invalid color '{}'", color)),
+        };
+    }
+
+    if let Ok(value) = color.parse::<u8>() {
+        return Ok(Color::AnsiValue(value));
+    }
+
+    Color::from_str(color).map_err(|_| format!("invalid color '{}'", color))
+}
+
+// the styles of the elements of the ui, set with specs like `cursor:green+bold` or `col2:blue`
+#[derive(Clone)]
+pub struct Theme {
+    pub cursor: ContentStyle,
+    pub marker: ContentStyle,
+    pub hint: ContentStyle,
+    pub matched: ContentStyle,
+    pub bar: ContentStyle,
+    pub border: ContentStyle,
+    pub header: ContentStyle,
+    columns: Vec<ContentStyle>,
+    // false once colors are dropped, which goes for the colors of the input with --ansi as well
+    colors: bool,
+}
+
+impl Theme {
+    pub fn new(matched: ContentStyle) -> Self {
+        Self {
+            cursor: ContentStyle::new().green(),
+            marker: ContentStyle::new(),
+            hint: ContentStyle::new().dark_grey(),
+            matched,
+            bar: ContentStyle::new().dim(),
+            border: ContentStyle::new().dim(),
+            header: ContentStyle::new().bold(),
+            columns: Vec::new(),
+            colors: true,
+        }
+    }
+
+    pub fn set(&mut self, spec: &str) -> Result<(), String> {
+        let Some((element, style)) = spec.split_once(':') else {
+            return Err(format!("invalid color '{}', expected 'element:style'", spec));
+        };
+
+        let style = parse_style(style)?;
+        match element.to_lowercase().as_str() {
+            "cursor" => self.cursor = style,
+            "marker" => self.marker = style,
+            "hint" => self.hint = style,
+            "match" => self.matched = style,
+            "bar" => self.bar = style,
+            "border" => self.border = style,
+            "header" => self.header = style,
+            column => match column.strip_prefix("col").and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) => {
+                    if self.columns.len() <= n {
+                        self.columns.resize(n + 1, ContentStyle::new());
+                    }
+
+                    self.columns[n] = style;
+                },
+                None => return Err(format!("unknown element '{}' in color '{}'", element, spec)),
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn column(&self, column: usize) -> ContentStyle {
+        self.columns.get(column).copied().unwrap_or_default()
+    }
+
+    // keeps attributes like bold and underline, but drops every color
+    pub fn without_colors(mut self) -> Self {
+        for style in [&mut self.cursor, &mut self.marker, &mut self.hint, &mut self.matched, &mut self.bar, &mut self.border, &mut self.header]
+            .into_iter()
+            .chain(self.columns.iter_mut()) {
+            *style = without_colors(*style);
+        }
+
+        self.colors = false;
+        self
+    }
+
+    // a style of the input itself, with --ansi
+    pub fn input(&self, style: ContentStyle) -> ContentStyle {
+        match self.colors {
+            true => style,
+            false => without_colors(style),
+        }
+    }
+}
+
+fn without_colors(mut style: ContentStyle) -> ContentStyle {
+    style.foreground_color = None;
+    style.background_color = None;
+    style.underline_color = None;
+
+    style
+}
+
+// applies `top` on top of `base`, where the colors of `top` take precedence and attributes are combined
+pub fn overlay(base: ContentStyle, top: ContentStyle) -> ContentStyle {
+    let mut style = base;
+    style.foreground_color = top.foreground_color.or(base.foreground_color);
+    style.background_color = top.background_color.or(base.background_color);
+    style.underline_color = top.underline_color.or(base.underline_color);
+    style.attributes.extend(top.attributes);
+
+    style
+}
@@ -0,0 +1,94 @@
+use crate::picker::picker::Line;
+
+// a template like `{2}:{4} ({0})`, where `{N}` is replaced by column N, `{}` by the whole line,
+// `{n}` by the zero-based index of the line and `{q}` by the current filter. anything else,
+// including unknown placeholders, is kept as is
+#[derive(Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone)]
+enum Part {
+    Text(String),
+    Column(usize),
+    Line,
+    Index,
+    Query,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let placeholder = rest.find('}').and_then(|end| Self::parse_placeholder(&rest[1..end]).map(|p| (p, end)));
+            match placeholder {
+                Some((part, end)) => {
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+
+                    parts.push(part);
+                    rest = &rest[end + 1..];
+                },
+                None => {
+                    text.push('{');
+                    rest = &rest[1..];
+                },
+            }
+        }
+
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Self { parts }
+    }
+
+    fn parse_placeholder(placeholder: &str) -> Option<Part> {
+        match placeholder {
+            "" => Some(Part::Line),
+            "n" => Some(Part::Index),
+            "q" => Some(Part::Query),
+            column => column.parse().ok().map(Part::Column),
+        }
+    }
+
+    // renders the template as bytes, as lines that aren't valid utf-8 are substituted as they were read
+    pub fn render(&self, line: &Line, index: usize, query: &str) -> Vec<u8> {
+        self.render_with(line, index, query, <[u8]>::to_vec)
+    }
+
+    // renders the template as a shell command, quoting every substituted value
+    pub fn render_quoted(&self, line: &Line, index: usize, query: &str) -> Vec<u8> {
+        self.render_with(line, index, query, |value| {
+            let mut quoted = vec![b'\''];
+            for b in value {
+                match b {
+                    b'\'' => quoted.extend(b"'\\''"),
+                    b => quoted.push(*b),
+                }
+            }
+
+            quoted.push(b'\'');
+            quoted
+        })
+    }
+
+    fn render_with(&self, line: &Line, index: usize, query: &str, value: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        self.parts.iter().flat_map(|part| match part {
+            Part::Text(text) => text.as_bytes().to_vec(),
+            Part::Column(column) => value(line.column_bytes(*column).unwrap_or_default()),
+            Part::Line => value(line.original_bytes()),
+            Part::Index => index.to_string().into_bytes(),
+            Part::Query => value(query.as_bytes()),
+        }).collect()
+    }
+}
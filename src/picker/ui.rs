@@ -1,104 +1,105 @@
 use anyhow::Result;
 use crossterm::terminal::ClearType;
 use crossterm::{cursor, style, terminal, QueueableCommand};
+use std::borrow::Cow;
 use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::ops::Range;
 
+use crate::picker::ansi::Styles;
 use crate::picker::modes::Mode;
-use crate::picker::options::{Options, PageSizeOption};
-use crate::picker::picker::Picker;
-use crossterm::style::Stylize;
+use crate::picker::options::{Options, PageSizeOption, PreviewPosition};
+use crate::picker::picker::{Line, Picker};
+use crate::picker::preview::Preview;
+use crate::picker::style::overlay;
+use crossterm::style::{ContentStyle, StyledContent};
 use regex::Regex;
 use std::io::Write;
-use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// the number of columns the list is scrolled horizontally at a time
+const SCROLL_STEP: usize = 8;
 
 pub struct Ui {
     cursor: usize,
     mode: Mode,
     input_buffer: String,
+    loading: bool,
+    initial_selection: Option<Regex>,
+    // once the cursor is moved, it stays on its line while lines are added
+    moved: bool,
 
-    // pagination
-    page: usize,
+    // pagination, `indexes` are the visible lines of which `page_size` lines are shown from `offset`
+    offset: usize,
     page_size: usize,
-    pages: Vec<Vec<usize>>,
+    indexes: Vec<usize>,
 
     // hinting
     hints: Option<HashMap<usize, String>>,
 
+    preview: Option<Preview>,
+
+    // the header lines, which are shown above the list
+    header: Vec<Line>,
+
     // terminal window
-    scroll_off: Option<u16>,
+    height: u16,
     top: u16,
     width: u16,
     bar: u16,
     col_widths: Vec<usize>,
+    shift: usize,
 
     // options
     opts: Options,
 }
 
 impl Ui {
-    pub fn new(picker: &Picker, opts: Options) -> Self {
-        let term_size = terminal::size().unwrap();
-        let position = cursor::position().unwrap();
-
-        let page_size = match opts.page_size {
-            PageSizeOption::Auto => {
-                if picker.lines().len() <= term_size.1 as usize {
-                    picker.lines().len()
-                } else {
-                    term_size.1 as usize - 4
-                }
-            },
-            PageSizeOption::Value(n) => n
-        };
-
-        let win_size = page_size + 2;
-        let scroll_off = (win_size as u16).checked_sub(term_size.1 - position.1);
-
-        let position = cursor::position().unwrap();
-
-        let mut col_widths = vec![0; picker.lines().first().unwrap().display(&opts.display_columns).len()];
-        for line in picker.lines() {
-            for (i, col) in line.display(&opts.display_columns).iter().enumerate() {
-                col_widths[i] = max(col_widths[i], col.len());
-            }
-        }
-
-        let mut initial_index = 0;
-        if let Some(selection) = &opts.selection_regex {
-            let selected_index = picker.lines().iter().enumerate().find_map(|(i, l)| {
-                match l.matches_regex(&Regex::new(selection.as_str()).unwrap()) {
-                    true => Some(i),
-                    false => None
-                }
-            });
+    pub fn new(opts: Options) -> Result<Self> {
+        let term_size = terminal::size()?;
+        let position = cursor::position()?;
 
-            if selected_index.is_some() {
-                initial_index = selected_index.unwrap();
-            }
-        }
+        let initial_selection = opts.selection_regex.clone();
+        let preview = opts.preview.clone().map(Preview::new);
+        let header = opts.header.iter()
+            .map(|h| Line::new(h.as_bytes(), &opts.format, opts.ansi))
+            .collect::<Vec<Line>>();
 
-
-        Ui {
+        let mut ui = Ui {
             mode: Mode::Normal,
-            cursor: initial_index,
+            cursor: 0,
             input_buffer: String::new(),
+            loading: true,
+            initial_selection,
+            moved: false,
 
             // pagination
-            page: 0,
-            page_size,
-            pages: Self::calc_pages((0..picker.lines().len()).collect::<Vec<usize>>().as_slice(), page_size),
+            offset: 0,
+            page_size: 0,
+            indexes: Vec::new(),
 
             // hinting
             hints: None,
 
-            scroll_off,
+            preview,
+            header: Vec::new(),
+
+            height: term_size.1,
             width: term_size.0,
-            top: position.1 - scroll_off.unwrap_or(0),
-            bar: 1 + position.1 - scroll_off.unwrap_or(0) + page_size as u16,
-            col_widths,
+            top: position.1,
+            bar: position.1 + 1,
+            col_widths: Vec::new(),
+            shift: 0,
             opts
+        };
+
+        for line in &header {
+            ui.widen_columns(&line.display(&ui.opts.display_columns));
         }
+        ui.header = header;
+
+        Ok(ui)
     }
 
     pub fn setup(&mut self, w: &mut impl Write) -> Result<()> {
@@ -106,14 +107,159 @@ impl Ui {
 
         w.queue(style::ResetColor)?.queue(cursor::Hide)?;
 
-        if let Some(scroll_off) = self.scroll_off {
-            w.queue(terminal::ScrollUp(scroll_off))?
-                .queue(cursor::MoveUp(scroll_off))?;
+        self.fit(w, 0)
+    }
+
+    // grows the window to fit the given number of lines, scrolling the terminal if there isn't enough room below
+    fn fit(&mut self, w: &mut impl Write, num_lines: usize) -> Result<()> {
+        let preview_height = self.preview_height();
+        let header_height = self.header.len() as u16;
+        let available = self.height.saturating_sub(4 + preview_height + header_height) as usize;
+        let page_size = match self.opts.page_size {
+            // a preview on the right gets the full height, regardless of the number of lines
+            PageSizeOption::Auto if self.preview.is_some() && preview_height == 0 => available,
+            PageSizeOption::Auto => min(num_lines, available),
+            PageSizeOption::Value(n) => n,
         };
 
+        let page_size = max(page_size, 1);
+        if page_size <= self.page_size {
+            return Ok(());
+        }
+
+        let win_size = page_size as u16 + 2 + preview_height + header_height;
+        let scroll = min((self.top + win_size).saturating_sub(self.height), self.top);
+        if scroll > 0 {
+            w.queue(terminal::ScrollUp(scroll))?;
+            self.top -= scroll;
+        }
+
+        self.page_size = page_size;
+        self.bar = self.top + 1 + header_height + page_size as u16;
+
+        Ok(())
+    }
+
+    fn widen_columns(&mut self, cols: &[String]) {
+        for (i, col) in cols.iter().enumerate() {
+            let col_width = sanitize(col, self.opts.tabstop).width();
+            match self.col_widths.get_mut(i) {
+                Some(width) => *width = max(*width, col_width),
+                None => self.col_widths.push(col_width),
+            }
+        }
+    }
+
+    // lays the window out again for the new terminal size, keeping the cursor on the same line
+    pub fn resize(&mut self, w: &mut impl Write, picker: &Picker, width: u16, height: u16) -> Result<()> {
+        self.width = width;
+        self.height = height;
+        self.top = min(self.top, height.saturating_sub(1));
+
+        w.queue(cursor::MoveTo(0, self.top))?
+            .queue(terminal::Clear(ClearType::FromCursorDown))?;
+
+        // the page size is only ever grown by fit, so it has to start over
+        self.page_size = 0;
+        self.fit(w, picker.lines().len())?;
+        self.go_to_position(self.position());
+
+        self.shift = min(self.shift, self.widest().saturating_sub(self.list_width()));
+
+        if let Mode::Hint(_) = self.mode {
+            self.hints = self.calculate_hints(self.opts.hint_alphabet.chars().collect());
+        }
+
         Ok(())
     }
 
+    // takes lines that were added to the picker into account, while keeping the cursor where it is
+    pub fn update(&mut self, w: &mut impl Write, picker: &Picker, added: Range<usize>) -> Result<()> {
+        for line in &picker.lines()[added.clone()] {
+            self.widen_columns(&line.display(&self.opts.display_columns));
+        }
+
+        self.fit(w, picker.lines().len())?;
+
+        let current = self.line_under_cursor();
+        picker.merge_filter(added, &mut self.indexes);
+
+        // until then, the cursor is on the first line matching --selection-regex or else the first line, even when
+        // better fuzzy matches arrive
+        if !self.moved {
+            let position = self.initial_selection.as_ref()
+                .and_then(|regex| self.indexes.iter().position(|i| picker.lines()[*i].matches_regex(regex)))
+                .unwrap_or(0);
+            self.go_to_position(position);
+
+            return Ok(());
+        }
+
+        match current {
+            // lines are added after the cursor, unless better fuzzy matches were inserted before it
+            Some(index) if self.line_under_cursor() != Some(index) => self.go_to_line(index),
+            Some(_) => {},
+            None => {
+                self.offset = min(self.offset, self.indexes.len().saturating_sub(1));
+                self.align_cursor();
+            },
+        }
+
+        Ok(())
+    }
+
+    // starts previewing the line under the cursor if it changed, returning true if the preview has to be redrawn
+    pub fn update_preview(&mut self, picker: &Picker) -> bool {
+        let line = self.line_under_cursor();
+        let Some(preview) = &mut self.preview else {
+            return false;
+        };
+
+        preview.show(line, picker);
+        preview.poll()
+    }
+
+    pub fn previewing(&self) -> bool {
+        self.preview.as_ref().is_some_and(Preview::running)
+    }
+
+    pub fn scroll_preview_up(&mut self) {
+        if let Some(preview) = &mut self.preview {
+            preview.scroll_up();
+        }
+    }
+
+    pub fn scroll_preview_down(&mut self) {
+        if let Some(preview) = &mut self.preview {
+            preview.scroll_down();
+        }
+    }
+
+    // the number of lines taken up by a preview below the list, including its border
+    fn preview_height(&self) -> u16 {
+        match (&self.preview, self.opts.preview_window.position) {
+            (Some(_), PreviewPosition::Bottom) => self.preview_size(),
+            _ => 0,
+        }
+    }
+
+    // the width or height of the preview, which leaves the list room for at least a line, or for the gutter and a
+    // character followed by an ellipsis next to the border
+    fn preview_size(&self) -> u16 {
+        match self.opts.preview_window.position {
+            PreviewPosition::Right => self.opts.preview_window.size(self.width, self.gutter() as u16 + 3),
+            PreviewPosition::Bottom => self.opts.preview_window.size(self.height, 5 + self.header.len() as u16),
+        }
+    }
+
+    pub fn loading(&self) -> bool {
+        self.loading
+    }
+
+    pub fn set_loading(&mut self, loading: bool) {
+        self.loading = loading;
+    }
+
     pub fn cleanup(&mut self, w: &mut impl Write) -> Result<()> {
         terminal::disable_raw_mode()?;
 
@@ -124,6 +270,8 @@ impl Ui {
             .queue(style::ResetColor)?
             .queue(cursor::Show)?;
 
+        w.flush()?;
+
         Ok(())
     }
 
@@ -131,8 +279,8 @@ impl Ui {
         w.queue(style::Print("Current selection:"))?
             .queue(cursor::MoveToNextLine(1))?;
 
-        for l in picker.selected().iter() {
-            w.queue(style::Print(l.output(&self.opts.output_columns, self.opts.delimiter.clone())))?
+        for l in picker.selected() {
+            w.queue(style::Print(sanitize(&l, self.opts.tabstop)))?
                 .queue(cursor::MoveToNextLine(1))?;
         }
 
@@ -149,7 +297,13 @@ impl Ui {
             return self.show_selections(w, picker);
         }
 
-        if let Some(page) = self.pages.get(self.page) {
+        for line in &self.header {
+            let cols = line.display(&self.opts.display_columns);
+            self.print_text(&cols, &[], &line.display_styles(&self.opts.display_columns), self.opts.theme.header, w)?;
+            w.queue(cursor::MoveToNextLine(1))?;
+        }
+
+        if let Some(page) = self.page() {
             for (page_lines_idx, all_lines_idx) in page.iter().enumerate() {
                 self.render_line(page_lines_idx, *all_lines_idx, w, picker)?;
             }
@@ -165,31 +319,83 @@ impl Ui {
                 _ => picker.filter_text()
             };
 
-            let filter_text = format!("filter:{}", filter_text);
-            let style_attr = match self.mode() {
-                Mode::Filter => style::Attribute::Reset,
-                _ => style::Attribute::Dim,
+            let filter_text = format!("filter({}):{}", picker.match_mode().name(), filter_text);
+            let filter_style = match self.mode() {
+                Mode::Filter => ContentStyle::new(),
+                _ => self.opts.theme.bar,
             };
 
-            w.queue(style::PrintStyledContent(filter_text.attribute(style_attr)))?;
+            w.queue(style::PrintStyledContent(StyledContent::new(filter_style, filter_text)))?;
         }
 
-        if self.num_pages() > 1 {
-            let pagination_text = format!("({}/{})", self.current_page() + 1, self.num_pages());
-            let pagination_len = pagination_text.len() as u16;
+        let mut status = Vec::new();
+        if self.loading {
+            status.push("loading...".to_string());
+        }
 
-            let styled = pagination_text.attribute(style::Attribute::Dim);
-            w.queue(cursor::MoveToColumn(self.width - pagination_len))?
+        if self.opts.scroll && self.indexes.len() > self.page_size {
+            status.push(format!("({}/{})", self.position() + 1, self.indexes.len()));
+        } else if !self.opts.scroll && self.num_pages() > 1 {
+            status.push(format!("({}/{})", self.current_page() + 1, self.num_pages()));
+        }
+
+        if !status.is_empty() {
+            let status_text = status.join(" ");
+            let status_len = status_text.len() as u16;
+
+            let styled = StyledContent::new(self.opts.theme.bar, status_text);
+            w.queue(cursor::MoveToColumn(self.width.saturating_sub(status_len)))?
                .queue(style::PrintStyledContent(styled))?;
         }
 
+        self.draw_preview(w)?;
+
         w.flush()?;
 
         Ok(())
     }
 
+    fn draw_preview(&self, w: &mut impl Write) -> Result<()> {
+        let Some(preview) = &self.preview else {
+            return Ok(());
+        };
+
+        let size = self.preview_size();
+
+        // the area the output is drawn in, next to or below a border
+        let (column, row, width, height) = match self.opts.preview_window.position {
+            PreviewPosition::Right => {
+                let column = self.width.saturating_sub(size);
+                for row in self.top..self.bar {
+                    w.queue(cursor::MoveTo(column, row))?
+                        .queue(terminal::Clear(ClearType::UntilNewLine))?
+                        .queue(style::PrintStyledContent(StyledContent::new(self.opts.theme.border, "│")))?;
+                }
+
+                (column + 2, self.top, size.saturating_sub(2), self.bar - self.top)
+            },
+            PreviewPosition::Bottom => {
+                let border = "─".repeat(self.width as usize);
+                w.queue(cursor::MoveTo(0, self.bar + 1))?
+                    .queue(style::PrintStyledContent(StyledContent::new(self.opts.theme.border, border)))?;
+
+                (0, self.bar + 2, self.width, size.saturating_sub(1))
+            },
+        };
+
+        for (i, line) in preview.lines().iter().take(height as usize).enumerate() {
+            w.queue(cursor::MoveTo(column, row + i as u16))?
+                .queue(style::Print(truncate(&sanitize(line, self.opts.tabstop), width as usize)))?;
+        }
+
+        Ok(())
+    }
+
     fn render_line(&self, page_lines_idx: usize, all_lines_idx: usize, w: &mut impl Write, picker: &Picker) -> Result<()> {
-        let cols = picker.lines().get(all_lines_idx).unwrap().display(&self.opts.display_columns);
+        let line = picker.lines().get(all_lines_idx).unwrap();
+        let cols = line.display(&self.opts.display_columns);
+        let styles = line.display_styles(&self.opts.display_columns);
+        let highlights = picker.highlights(all_lines_idx);
         let selected = picker.is_selected(all_lines_idx);
 
         // todo: maybe only clear lines that need to change
@@ -198,13 +404,13 @@ impl Ui {
         match self.mode() {
             Mode::Hint(_) => {
                 match self.get_hint(page_lines_idx) {
-                    Some(hint) => self.render_hinted_line(cols.as_slice(), hint, selected, w)?,
-                    None => self.render_normal_line(cols.as_slice(), false, selected, w)?
+                    Some(hint) => self.render_hinted_line(cols.as_slice(), &highlights, &styles, hint, selected, w)?,
+                    None => self.render_normal_line(cols.as_slice(), &highlights, &styles, false, selected, w)?
                 }
             },
             _ => {
                 let current = page_lines_idx == self.cursor;
-                self.render_normal_line(cols.as_slice(), current, selected, w)?;
+                self.render_normal_line(cols.as_slice(), &highlights, &styles, current, selected, w)?;
             },
         };
 
@@ -213,54 +419,192 @@ impl Ui {
         Ok(())
     }
 
-    fn render_normal_line(&self, cols: &[String], current: bool, selected: bool, w: &mut impl Write) -> Result<()> {
-        if current {
-            w.queue(style::SetForegroundColor(
-                style::Color::from_str("green").unwrap(),
-            ))?;
-
-            w.queue(style::Print('>'))?;
+    fn render_normal_line(&self, cols: &[String], highlights: &[Vec<usize>], styles: &[Styles], current: bool, selected: bool, w: &mut impl Write) -> Result<()> {
+        let style = current.then_some(self.opts.theme.cursor);
+        if let Some(style) = style {
+            w.queue(style::PrintStyledContent(StyledContent::new(style, self.opts.pointer.as_str())))?;
         }
 
         if selected {
-            w.queue(style::Print('+'))?;
+            self.print_marker(w)?;
         }
 
-        self.print_text(cols, w)?;
+        self.print_text(cols, highlights, styles, style.unwrap_or_default(), w)
+    }
 
-        if current {
-            w.queue(style::SetForegroundColor(style::Color::Reset))?;
+    fn render_hinted_line(&self, cols: &[String], highlights: &[Vec<usize>], styles: &[Styles], hint: String, selected: bool, w: &mut impl Write) -> Result<()> {
+        if selected {
+            self.print_marker(w)?;
         }
 
+        // first print the whole line
+        self.print_text(cols, highlights, styles, ContentStyle::new(), w)?;
+
+        // then print the hint, overwriting the beginning of the printed line (excluding marker)
+        w.queue(cursor::MoveToColumn(self.gutter() as u16))?
+            .queue(style::PrintStyledContent(StyledContent::new(self.opts.theme.hint, hint)))?;
+
         Ok(())
     }
 
-    fn render_hinted_line(&self, cols: &[String], hint: String, selected: bool, w: &mut impl Write) -> Result<()> {
-        if selected {
-            w.queue(style::Print('+'))?;
+    fn print_marker(&self, w: &mut impl Write) -> Result<()> {
+        w.queue(cursor::MoveToColumn(self.opts.pointer.width() as u16))?
+            .queue(style::PrintStyledContent(StyledContent::new(self.opts.theme.marker, self.opts.marker.as_str())))?;
+
+        Ok(())
+    }
+
+    // the width of the pointer and marker in front of every line
+    fn gutter(&self) -> usize {
+        self.opts.pointer.width() + self.opts.marker.width()
+    }
+
+    // prints the columns of a line shifted by the horizontal scroll, cutting it off with an ellipsis where it
+    // doesn't fit. `style` is the style of the line, which the style of each column is applied on top of
+    fn print_text(&self, cols: &[String], highlights: &[Vec<usize>], styles: &[Styles], style: ContentStyle, w: &mut impl Write) -> Result<()> {
+        // the part of the line that is visible, leaving room for the ellipses
+        let gutter = self.gutter();
+        let start = if self.shift > 0 { self.shift + gutter + 1 } else { gutter };
+        let mut end = self.shift + self.list_width();
+        let cut_off = self.line_width(cols) > end;
+        if cut_off {
+            end = end.saturating_sub(1);
         }
 
-        // first print the whole line
-        self.print_text(cols, w)?;
+        // there's no room for the text, e.g. when the preview takes up the width of the terminal
+        if start >= end {
+            return Ok(());
+        }
 
-        // then print the hint, overwriting the beginning of the printed line (excluding marker)
-        w.queue(cursor::MoveToColumn(2))?
-            .queue(style::SetForegroundColor(style::Color::DarkGrey))?
-            .queue(style::Print(hint))?
-            .queue(style::SetForegroundColor(style::Color::Reset))?;
+        let mut position = gutter;
+        for (i, col) in cols.iter().enumerate() {
+            let chars = highlights.get(i).map(Vec::as_slice).unwrap_or_default();
+            // colors of the input itself, with --ansi, take precedence over the style of the column
+            let col_style = overlay(self.opts.theme.column(self.column_index(i)), style);
+            let col_styles = std::iter::once((0, col_style))
+                .chain(styles.get(i).into_iter().flatten().map(|(c, s)| (*c, overlay(col_style, self.opts.theme.input(*s)))))
+                .collect::<Styles>();
+            self.print_column(col, position, start..end, chars, &col_styles, w)?;
+
+            position += self.col_widths[i] + 2;
+        }
+
+        if self.shift > 0 {
+            w.queue(cursor::MoveToColumn(gutter as u16))?
+                .queue(style::PrintStyledContent(StyledContent::new(style, '…')))?;
+        }
+
+        if cut_off {
+            w.queue(cursor::MoveToColumn((end - self.shift) as u16))?
+                .queue(style::PrintStyledContent(StyledContent::new(style, '…')))?;
+        }
 
         Ok(())
     }
 
-    fn print_text(&self, cols: &[String], w: &mut impl Write) -> Result<()> {
-        let mut position = 2;
+    // the column of the line shown as the given displayed column
+    fn column_index(&self, displayed: usize) -> usize {
+        match &self.opts.display_columns {
+            Some(range) => (0..).filter(|c| range.contains(*c)).nth(displayed).unwrap_or(displayed),
+            None => displayed,
+        }
+    }
+
+    // prints the part of a column starting at `position` that falls within `visible`, where `styles` are the styles
+    // of the column from the char index each starts at
+    fn print_column(&self, text: &str, mut position: usize, visible: Range<usize>, highlights: &[usize], styles: &[(usize, ContentStyle)], w: &mut impl Write) -> Result<()> {
+        // characters are printed in runs, so the style only has to be set once per run
+        let mut run = String::new();
+        let mut run_highlighted = false;
+        let mut run_style = ContentStyle::new();
+        let mut style = ContentStyle::new();
+        let mut styles = styles.iter().peekable();
+        let mut moved = false;
+        let mut char_index = 0;
+        let column_start = position;
+        for grapheme in text.graphemes(true) {
+            while let Some((_, next)) = styles.next_if(|(start, _)| *start <= char_index) {
+                style = *next;
+            }
+
+            let chars = grapheme.chars().count();
+            let highlighted = (char_index..char_index + chars).any(|c| highlights.binary_search(&c).is_ok());
+            char_index += chars;
+
+            let shown = sanitize_grapheme(grapheme, position - column_start, self.opts.tabstop);
+            let x = position;
+            position += shown.width();
+            if x < visible.start {
+                continue;
+            }
+
+            if position > visible.end {
+                break;
+            }
+
+            if !moved {
+                w.queue(cursor::MoveToColumn((x - self.shift) as u16))?;
+                moved = true;
+            }
+
+            if (highlighted, style) != (run_highlighted, run_style) && !run.is_empty() {
+                self.print_run(run.as_str(), run_highlighted, run_style, w)?;
+                run.clear();
+            }
+
+            run_highlighted = highlighted;
+            run_style = style;
+            run.push_str(&shown);
+        }
+
+        self.print_run(run.as_str(), run_highlighted, run_style, w)
+    }
+
+    // the width of a line when printed, including the marker
+    fn line_width(&self, cols: &[String]) -> usize {
+        let mut position = self.gutter();
+        let mut width = position;
         for (i, col) in cols.iter().enumerate() {
-            w.queue(cursor::MoveToColumn(position))?
-                .queue(style::Print(col))?;
+            width = position + sanitize(col, self.opts.tabstop).width();
+            position += self.col_widths[i] + 2;
+        }
+
+        width
+    }
+
+    // the number of terminal columns available to the list
+    fn list_width(&self) -> usize {
+        match (&self.preview, self.opts.preview_window.position) {
+            (Some(_), PreviewPosition::Right) => self.width.saturating_sub(self.preview_size() + 1) as usize,
+            _ => self.width as usize,
+        }
+    }
+
+    // the width of the widest line, as the sum of the widest value in each column
+    fn widest(&self) -> usize {
+        (self.gutter() + self.col_widths.iter().map(|w| w + 2).sum::<usize>()).saturating_sub(2)
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.shift = self.shift.saturating_sub(SCROLL_STEP);
+    }
 
-            position += (self.col_widths[i] + 2) as u16;
+    pub fn scroll_right(&mut self) {
+        self.shift = min(self.shift + SCROLL_STEP, self.widest().saturating_sub(self.list_width()));
+    }
+
+    fn print_run(&self, run: &str, highlighted: bool, style: ContentStyle, w: &mut impl Write) -> Result<()> {
+        if run.is_empty() {
+            return Ok(());
         }
 
+        let style = match highlighted {
+            true => overlay(style, self.opts.theme.matched),
+            false => style,
+        };
+
+        w.queue(style::PrintStyledContent(StyledContent::new(style, run)))?;
+
         Ok(())
     }
 
@@ -286,6 +630,7 @@ impl Ui {
 
     pub fn set_cursor(&mut self, i: usize) {
         self.cursor = i;
+        self.moved = true;
     }
 
     fn align_cursor(&mut self) {
@@ -297,54 +642,112 @@ impl Ui {
     }
 
     pub fn line_under_cursor(&self) -> Option<usize> {
-        if let Some(page) = self.page() {
-            return page.get(self.cursor).map(|i| i.clone())
-        };
+        self.page()?.get(self.cursor).copied()
+    }
+
+    fn go_to_line(&mut self, index: usize) {
+        match self.indexes.iter().position(|i| *i == index) {
+            Some(position) => self.go_to_position(position),
+            None => self.align_cursor(),
+        }
+    }
+
+    // the position of the cursor among all visible lines
+    fn position(&self) -> usize {
+        self.offset + self.cursor
+    }
 
-        None
+    // moves the cursor to a position among all visible lines, moving the view along with it
+    fn go_to_position(&mut self, position: usize) {
+        let position = min(position, self.indexes.len().saturating_sub(1));
+
+        if self.opts.scroll {
+            // keep a margin of lines around the cursor, unless the view is at either end
+            let margin = min(self.opts.scroll_off, (self.page_size - 1) / 2);
+            if position < self.offset + margin {
+                self.offset = position.saturating_sub(margin);
+            }
+
+            if position + margin >= self.offset + self.page_size {
+                self.offset = position + margin + 1 - self.page_size;
+            }
+
+            self.offset = min(self.offset, self.indexes.len().saturating_sub(self.page_size));
+        } else {
+            self.offset = position - position % self.page_size;
+        }
+
+        self.cursor = position - self.offset;
     }
 
     pub fn move_cursor_up(&mut self) {
-        self.cursor = Self::saturating_decrement(self.cursor);
+        self.initial_selection = None;
+        self.moved = true;
+
+        match self.opts.scroll {
+            true => self.go_to_position(self.position().saturating_sub(1)),
+            false => self.cursor = Self::saturating_decrement(self.cursor),
+        }
     }
 
     pub fn move_cursor_down(&mut self) {
-        if let Some(page) = self.page() {
-            self.cursor = Self::increment_to_max(self.cursor, min(page.len() - 1, self.page_size - 1));
+        self.initial_selection = None;
+        self.moved = true;
+
+        if self.opts.scroll {
+            self.go_to_position(self.position() + 1);
+        } else if let Some(page) = self.page() {
+            self.cursor = Self::increment_to_max(self.cursor, page.len() - 1);
         }
     }
 
     pub fn paginate(&mut self, indexes: &[usize]) {
-        self.pages = Self::calc_pages(indexes, self.page_size);
+        self.indexes = indexes.to_vec();
 
-        self.page = 0;
+        self.offset = 0;
+        self.initial_selection = None;
         self.align_cursor();
     }
 
     pub fn previous_page(&mut self) {
-        self.page = Self::saturating_decrement(self.page);
-        self.align_cursor()
+        self.initial_selection = None;
+        self.moved = true;
+
+        let position = self.position().saturating_sub(self.page_size);
+        self.offset = self.offset.saturating_sub(self.page_size);
+
+        match self.opts.scroll {
+            true => self.go_to_position(position),
+            false => self.align_cursor(),
+        }
     }
 
     pub fn next_page(&mut self) {
-        self.page = Self::increment_to_max(self.page, self.num_pages().saturating_sub(1));
-        self.align_cursor()
+        self.initial_selection = None;
+        self.moved = true;
+
+        let position = self.position() + self.page_size;
+        if self.offset + self.page_size < self.indexes.len() {
+            self.offset += self.page_size;
+        }
+
+        match self.opts.scroll {
+            true => self.go_to_position(position),
+            false => self.align_cursor(),
+        }
     }
 
     pub fn page(&self) -> Option<&[usize]> {
-        self.pages.get(self.page).map(|p| p.as_slice())
+        let end = min(self.offset + self.page_size, self.indexes.len());
+        (self.offset < end).then(|| &self.indexes[self.offset..end])
     }
 
     pub fn num_pages(&self) -> usize {
-        self.pages.len()
+        self.indexes.len().div_ceil(self.page_size)
     }
 
     pub fn current_page(&self) -> usize {
-        self.page
-    }
-
-    fn calc_pages(indexes: &[usize], page_size: usize) -> Vec<Vec<usize>> {
-        indexes.chunks(page_size).map(|c| c.to_vec()).collect()
+        self.offset / self.page_size
     }
 
     pub fn set_input_buffer(&mut self, s: String) {
@@ -444,3 +847,59 @@ impl Ui {
         min(n + 1, max)
     }
 }
+
+// cuts text off at the given display width, without splitting characters
+fn truncate(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, grapheme) in text.grapheme_indices(true) {
+        used += grapheme.width();
+        if used > width {
+            return &text[..i];
+        }
+    }
+
+    text
+}
+
+// text as it is shown, where control characters are replaced by placeholders like `^[` so they can't move the cursor
+// or change the terminal, and tabs are expanded to the next tab stop
+fn sanitize(text: &str, tabstop: usize) -> Cow<'_, str> {
+    if !text.contains(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut shown = String::new();
+    let mut width = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme = sanitize_grapheme(grapheme, width, tabstop);
+        width += grapheme.width();
+        shown.push_str(&grapheme);
+    }
+
+    Cow::Owned(shown)
+}
+
+// a grapheme as it is shown at the given column of the text it is part of
+fn sanitize_grapheme(grapheme: &str, column: usize, tabstop: usize) -> Cow<'_, str> {
+    if !grapheme.contains(char::is_control) {
+        return Cow::Borrowed(grapheme);
+    }
+
+    let mut shown = String::new();
+    for c in grapheme.chars() {
+        match c {
+            '\t' => shown.push_str(&" ".repeat(tabstop - (column + shown.width()) % tabstop)),
+            '\n' => shown.push_str("\\n"),
+            '\r' => shown.push_str("\\r"),
+            '\x7f' => shown.push_str("^?"),
+            c if (c as u32) < 0x20 => {
+                shown.push('^');
+                shown.push((c as u8 + 0x40) as char);
+            },
+            c if c.is_control() => shown.push_str(&format!("\\x{:02x}", c as u32)),
+            c => shown.push(c),
+        }
+    }
+
+    Cow::Owned(shown)
+}